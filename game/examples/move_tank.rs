@@ -1,8 +1,6 @@
-use std::collections::HashSet;
 use std::path;
 
 use ggez::conf::WindowSetup;
-use ggez::event::KeyCode;
 use ggez::graphics::Rect;
 use ggez::nalgebra as na;
 use ggez::{
@@ -10,6 +8,7 @@ use ggez::{
     graphics::{self, screen_coordinates},
 };
 
+use game::network::protocol::PlayerInput;
 use game::{objects::Tank, state::window_state_mode};
 
 pub fn main() -> ggez::GameResult {
@@ -39,64 +38,83 @@ pub fn main() -> ggez::GameResult {
         turret_texture: Some(graphics::Image::new(ctx, "/TankTops.png")?),
         turret_direction: na::Vector2::from([-1., 0.]),
         turret_rotation: 0.,
-        turret_rotation_origin: na::Vector2::from([
-            tank_dimensions.w * 0.7,
-            tank_dimensions.h / 2.,
-        ]),
         player: game::state::Player::P1,
         turret_width: tank_dimensions.w * 0.7,
+        health: game::objects::MAX_HEALTH,
+        armor: 0,
+        weapon: game::objects::weapon::Weapon::default(),
     };
     let dim = Rect::new(0., 0., 10., 10.);
 
     tank.movement(
-        &vec![KeyCode::W, KeyCode::P]
-            .into_iter()
-            .collect::<HashSet<KeyCode>>(),
+        &PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        },
         sc,
         dim,
-        &Rect::new(0., 0., 0., 0.),
+        &[],
+        game::network::INPUT_DELTA,
     );
     tank.rotation(
-        &vec![KeyCode::Up, KeyCode::D]
-            .into_iter()
-            .collect::<HashSet<KeyCode>>(),
+        &PlayerInput {
+            forward: true,
+            turn_right: true,
+            ..PlayerInput::default()
+        },
         0.3,
     );
     tank.movement(
-        &vec![KeyCode::Up, KeyCode::D]
-            .into_iter()
-            .collect::<HashSet<KeyCode>>(),
+        &PlayerInput {
+            forward: true,
+            turn_right: true,
+            ..PlayerInput::default()
+        },
         sc,
         dim,
-        &Rect::new(0., 0., 0., 0.),
+        &[],
+        game::network::INPUT_DELTA,
     );
     tank.rotation(
-        &vec![KeyCode::Up, KeyCode::Right]
-            .into_iter()
-            .collect::<HashSet<KeyCode>>(),
+        &PlayerInput {
+            forward: true,
+            turn_right: true,
+            ..PlayerInput::default()
+        },
         0.3,
     );
     tank.movement(
-        &vec![KeyCode::W].into_iter().collect::<HashSet<KeyCode>>(),
+        &PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        },
         sc,
         dim,
-        &Rect::new(0., 0., 0., 0.),
+        &[],
+        game::network::INPUT_DELTA,
     );
     tank.movement(
-        &vec![KeyCode::S].into_iter().collect::<HashSet<KeyCode>>(),
+        &PlayerInput {
+            backward: true,
+            ..PlayerInput::default()
+        },
         sc,
         dim,
-        &Rect::new(0., 0., 0., 0.),
+        &[],
+        game::network::INPUT_DELTA,
     );
     tank.rotation(
-        &vec![KeyCode::Up, KeyCode::Left]
-            .into_iter()
-            .collect::<HashSet<KeyCode>>(),
+        &PlayerInput {
+            forward: true,
+            turn_left: true,
+            ..PlayerInput::default()
+        },
         0.3,
     );
 
     let mut test_state = test_ggez::TestState::new(tank, "move_tank");
 
     let _ = event::run(ctx, events_loop, &mut test_state);
+    test_state.finish().unwrap_or_else(|err| panic!("{}", err));
     Ok(())
 }
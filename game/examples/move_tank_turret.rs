@@ -36,12 +36,11 @@ pub fn main() -> ggez::GameResult {
         turret_texture: Some(graphics::Image::new(ctx, "/TankTops.png")?),
         turret_direction: na::Vector2::from([-1., 0.]),
         turret_rotation: 0.,
-        turret_rotation_origin: na::Vector2::from([
-            tank_dimensions.w * 0.7,
-            tank_dimensions.h / 2.,
-        ]),
         player: game::state::Player::P1,
         turret_width: tank_dimensions.w * 0.7,
+        health: game::objects::MAX_HEALTH,
+        armor: 0,
+        weapon: game::objects::weapon::Weapon::default(),
     };
 
     tank.update_turret_direction(Point2::from_slice(&[300., 400.]));
@@ -49,5 +48,6 @@ pub fn main() -> ggez::GameResult {
     let mut test_state = test_ggez::TestState::new(tank, "move_tank_turret");
 
     let _ = event::run(ctx, events_loop, &mut test_state);
+    test_state.finish().unwrap_or_else(|err| panic!("{}", err));
     Ok(())
 }
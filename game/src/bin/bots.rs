@@ -0,0 +1,40 @@
+use game::bot::{self, Bot, Difficulty};
+use game::map::{Map, DEFAULT_MAP};
+use game::network::{INPUT_DELTA, TICK_RATE};
+use game::simulation::World;
+use game::state::{match_players, Player};
+
+/// Longest a match may last, in seconds.
+const TIME_LIMIT: u32 = 300;
+
+/// Plays a headless bot-vs-bot match, one bot per difficulty given, e.g.
+/// `cargo run --bin bots -- hard easy normal`.
+pub fn main() -> ggez::GameResult {
+    let difficulties = std::env::args()
+        .skip(1)
+        .map(|name| name.parse())
+        .collect::<ggez::GameResult<Vec<Difficulty>>>()?;
+    let difficulties = if difficulties.is_empty() {
+        vec![Difficulty::Normal, Difficulty::Normal]
+    } else {
+        difficulties
+    };
+
+    let players = match_players(Player::P1, difficulties.len())?;
+    let map: Map = std::fs::read_to_string(format!("../resources{}", DEFAULT_MAP))?.parse()?;
+    let mut world = World::with_map(players, map.tiles.bounds(), map);
+    let mut bots: Vec<Bot> = players
+        .iter()
+        .zip(difficulties)
+        .map(|(player, difficulty)| Bot::new(*player, difficulty))
+        .collect();
+
+    match bot::play(&mut world, &mut bots, INPUT_DELTA, TIME_LIMIT * TICK_RATE) {
+        Some(winner) => println!("{:?} wins", winner),
+        None => println!("draw"),
+    }
+    for tank in &world.tanks {
+        println!("{:?}: {} health", tank.player, tank.health);
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use game::editor::EditorState;
+use game::map::{DEFAULT_MAP, TILE_SIZE};
+use game::state::window_state_mode;
+use ggez::conf::WindowSetup;
+use ggez::event;
+
+/// Edits a map file in the resources, e.g. `cargo run --bin editor -- /maps/arena.map`.
+/// A file that doesn't exist yet starts as a blank map the size of the window.
+pub fn main() -> ggez::GameResult {
+    let map = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_MAP.to_owned());
+    let path = PathBuf::from(format!("../resources{}", map));
+    let mode = window_state_mode();
+    let cb = ggez::ContextBuilder::new("tank_battle_editor", "naomijub")
+        .add_resource_path("../resources")
+        .window_setup(WindowSetup {
+            title: format!("Tank Battle Map Editor - {}", map),
+            ..WindowSetup::default()
+        })
+        .window_mode(mode);
+
+    let (ctx, event_loop) = &mut cb.build()?;
+    let columns = (mode.width / TILE_SIZE) as usize;
+    let rows = (mode.height / TILE_SIZE) as usize;
+    let mut state = EditorState::new(ctx, path, columns, rows)?;
+    event::run(ctx, event_loop, &mut state)
+}
@@ -0,0 +1,25 @@
+use game::map::{Map, DEFAULT_MAP};
+use game::network::server::Server;
+use game::network::DEFAULT_SERVER_ADDR;
+
+/// Runs the authoritative server on an address and a map in the resources,
+/// e.g. `cargo run --bin server -- 0.0.0.0:7878 --map /maps/outpost.map`.
+pub fn main() -> ggez::GameResult {
+    let addr = std::env::args()
+        .nth(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .unwrap_or_else(|| DEFAULT_SERVER_ADDR.to_owned());
+    let map = std::env::args()
+        .skip_while(|arg| arg != "--map")
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_MAP.to_owned());
+    let tiles: Map = std::fs::read_to_string(format!("../resources{}", map))?.parse()?;
+    let mut server = Server::with_map(&addr, tiles)?;
+
+    println!(
+        "Tank Battle Ground server listening on {} with {}",
+        server.local_addr()?,
+        map
+    );
+    Ok(server.run()?)
+}
@@ -0,0 +1,560 @@
+use std::str::FromStr;
+
+use ggez::graphics::Rect;
+use ggez::nalgebra as na;
+use ggez::GameError;
+
+use crate::collision::Obb;
+use crate::map::TILE_SIZE;
+use crate::network::protocol::PlayerInput;
+use crate::objects::bullet::SPEED as BULLET_SPEED;
+use crate::objects::{tank_hitboxes, Tank, TANK_DIMENSIONS, TANK_SPEED};
+use crate::simulation::World;
+use crate::state::Player;
+
+/// Distance a bot tries to keep from its target.
+const PREFERRED_DISTANCE: f32 = 350.;
+/// How far from `PREFERRED_DISTANCE` a bot lets its target get before moving.
+const DISTANCE_SLACK: f32 = 50.;
+/// How much closer another enemy has to be before a bot switches targets.
+const RETARGET_MARGIN: f32 = 100.;
+/// Seconds of driving a bot checks for obstacles ahead.
+const LOOKAHEAD: f32 = 0.5;
+/// Seconds a blocked bot spends driving across its target's direction before
+/// heading for it again.
+const DETOUR_TIME: f32 = 2.;
+/// Heading error, in radians, below which a bot drives instead of turning on the spot.
+const DRIVE_ANGLE: f32 = 0.5;
+
+/// How well a bot plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Radians the aim wanders around the target.
+    fn aim_error(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.25,
+            Difficulty::Normal => 0.08,
+            Difficulty::Hard => 0.,
+        }
+    }
+
+    /// Whether it aims where the target is going rather than where it is.
+    fn leads_shots(self) -> bool {
+        self != Difficulty::Easy
+    }
+
+    /// Seconds between shots, on top of the weapon's own fire rate.
+    fn fire_interval(self) -> f32 {
+        match self {
+            Difficulty::Easy => 1.5,
+            Difficulty::Normal => 0.6,
+            Difficulty::Hard => 0.,
+        }
+    }
+
+    /// How close the target has to be to shoot at it.
+    fn range(self) -> f32 {
+        match self {
+            Difficulty::Easy => 400.,
+            Difficulty::Normal => 600.,
+            Difficulty::Hard => 900.,
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = GameError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(GameError::ConfigError(format!(
+                "difficulty is easy, normal or hard, got {}",
+                name
+            ))),
+        }
+    }
+}
+
+/// Drives `player`'s tank with the same `PlayerInput`s a human would send.
+#[derive(Debug, Clone)]
+pub struct Bot {
+    pub player: Player,
+    pub difficulty: Difficulty,
+    target: Option<Player>,
+    /// Where every tank was on the previous input, to tell how fast they move.
+    last_seen: Vec<(Player, na::Point2<f32>)>,
+    /// Seconds until the bot lets itself fire again.
+    trigger: f32,
+    /// Seconds of driving left on the way round an obstacle.
+    detour: f32,
+    /// Which way round obstacles it goes, 1 to the target's right, -1 to its left.
+    side: f32,
+    /// Inputs produced so far, makes the aim wander.
+    ticks: u32,
+}
+
+impl Bot {
+    pub fn new(player: Player, difficulty: Difficulty) -> Bot {
+        Bot {
+            player,
+            difficulty,
+            target: None,
+            last_seen: Vec::new(),
+            trigger: 0.,
+            detour: 0.,
+            side: 1.,
+            ticks: 0,
+        }
+    }
+
+    pub fn target(&self) -> Option<Player> {
+        self.target
+    }
+
+    /// What the bot does for the next `delta` seconds of `world`. Without a
+    /// tank or anyone to fight it does nothing.
+    pub fn input(&mut self, world: &World, delta: f32) -> PlayerInput {
+        self.ticks += 1;
+        self.trigger = (self.trigger - delta).max(0.);
+
+        let tank = match world.tank(self.player) {
+            Some(tank) => tank,
+            None => return PlayerInput::default(),
+        };
+        self.target = self.choose_target(world, tank);
+        let target = match self.target.and_then(|player| world.tank(player)) {
+            Some(target) => target,
+            None => return PlayerInput::default(),
+        };
+        let velocity = self.velocity(target, delta);
+        self.last_seen = world
+            .tanks
+            .iter()
+            .map(|tank| (tank.player, tank.position))
+            .collect();
+
+        let to_target = target.position - tank.position;
+        let aim_at = if self.difficulty.leads_shots() {
+            lead(tank.position, target.position, velocity)
+        } else {
+            target.position
+        };
+        let wander = self.difficulty.aim_error() * (self.ticks as f32 * 0.37).sin();
+        let aim =
+            tank.turret_origin() + na::Rotation2::new(wander) * (aim_at - tank.turret_origin());
+        let fire = self.trigger <= 0.
+            && to_target.norm() <= self.difficulty.range()
+            && tank.weapon.can_fire()
+            && line_of_fire(world, tank.position, target.position);
+        if fire {
+            self.trigger = self.difficulty.fire_interval();
+        }
+
+        let mut input = PlayerInput {
+            fire,
+            aim: [aim.x, aim.y],
+            ..PlayerInput::default()
+        };
+        self.steer(&mut input, world, tank, to_target, delta);
+        input
+    }
+
+    /// The nearest enemy, unless the current target is nearly as close.
+    fn choose_target(&self, world: &World, tank: &Tank) -> Option<Player> {
+        let distance = |other: &Tank| (other.position - tank.position).norm();
+        let nearest = world
+            .tanks
+            .iter()
+            .filter(|other| other.player != self.player)
+            .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())?;
+
+        match self.target.and_then(|player| world.tank(player)) {
+            Some(current) if distance(current) <= distance(nearest) + RETARGET_MARGIN => {
+                Some(current.player)
+            }
+            _ => Some(nearest.player),
+        }
+    }
+
+    fn velocity(&self, target: &Tank, delta: f32) -> na::Vector2<f32> {
+        match self
+            .last_seen
+            .iter()
+            .find(|(player, _)| *player == target.player)
+        {
+            Some((_, last)) if delta > 0. => (target.position - last) / delta,
+            _ => na::Vector2::from([0., 0.]),
+        }
+    }
+
+    /// Faces the target and keeps `PREFERRED_DISTANCE` from it. When the way
+    /// is blocked it drives across the target's direction for a while, keeping
+    /// to the same side until that is blocked too, to get round walls.
+    fn steer(
+        &mut self,
+        input: &mut PlayerInput,
+        world: &World,
+        tank: &Tank,
+        to_target: na::Vector2<f32>,
+        delta: f32,
+    ) {
+        let distance = to_target.norm();
+        let heading = heading(tank.tank_rotation);
+        let towards = to_target.y.atan2(to_target.x);
+        let (wanted, drive) = if self.detour > 0. {
+            (towards + self.side * std::f32::consts::FRAC_PI_2, 1.)
+        } else if distance > PREFERRED_DISTANCE + DISTANCE_SLACK {
+            (towards, 1.)
+        } else if distance < PREFERRED_DISTANCE - DISTANCE_SLACK {
+            (towards, -1.)
+        } else {
+            (towards, 0.)
+        };
+        let turn = wrap_angle(wanted - heading);
+        input.turn_right = turn > 0.05;
+        input.turn_left = turn < -0.05;
+
+        if drive == 0. || turn.abs() >= DRIVE_ANGLE {
+            return;
+        }
+        if !is_blocked(world, tank, heading, drive) {
+            input.forward = drive > 0.;
+            input.backward = drive < 0.;
+            self.detour = (self.detour - delta).max(0.);
+        } else if self.detour > 0. {
+            self.side = -self.side;
+            self.detour = DETOUR_TIME;
+        } else {
+            self.detour = DETOUR_TIME;
+        }
+    }
+}
+
+/// Where to aim from `shooter` so a bullet meets a target at `target` moving
+/// at `velocity`, or the target itself if the bullet can never catch it.
+pub fn lead(
+    shooter: na::Point2<f32>,
+    target: na::Point2<f32>,
+    velocity: na::Vector2<f32>,
+) -> na::Point2<f32> {
+    // |offset + velocity * t| = BULLET_SPEED * t
+    let offset = target - shooter;
+    let a = velocity.dot(&velocity) - BULLET_SPEED * BULLET_SPEED;
+    let b = 2. * offset.dot(&velocity);
+    let c = offset.dot(&offset);
+    let discriminant = b * b - 4. * a * c;
+    if a >= 0. || discriminant < 0. {
+        return target;
+    }
+
+    let time = (-b - discriminant.sqrt()) / (2. * a);
+    target + velocity * time.max(0.)
+}
+
+/// The angle a tank with `tank_rotation` drives towards.
+fn heading(tank_rotation: f32) -> f32 {
+    wrap_angle(tank_rotation + std::f32::consts::PI)
+}
+
+/// Whether driving `direction` (1 forwards, -1 backwards) along `heading`
+/// for `LOOKAHEAD` seconds would hit another tank or the map, or leave the
+/// arena.
+fn is_blocked(world: &World, tank: &Tank, heading: f32, direction: f32) -> bool {
+    let (sin, cos) = heading.sin_cos();
+    let ahead = tank.position + na::Vector2::from([cos, sin]) * direction * TANK_SPEED * LOOKAHEAD;
+    let hitbox = Obb::new(
+        ahead,
+        TANK_DIMENSIONS.w,
+        TANK_DIMENSIONS.h,
+        heading - std::f32::consts::PI,
+    );
+
+    !contains(world.coordinate, hitbox.bounding_rect())
+        || world.map.blocks_tank(&hitbox)
+        || tank_hitboxes(tank.player, world.tanks.iter())
+            .iter()
+            .any(|other| hitbox.overlaps(other))
+}
+
+/// Whether a bullet from `from` would reach `to` without hitting a wall or cover.
+fn line_of_fire(world: &World, from: na::Point2<f32>, to: na::Point2<f32>) -> bool {
+    let steps = ((to - from).norm() / (TILE_SIZE / 2.)).ceil() as usize;
+    (1..steps).all(|step| {
+        let point = from + (to - from) * (step as f32 / steps as f32);
+        !world.map.blocks_bullet(point)
+    })
+}
+
+fn contains(outer: Rect, inner: Rect) -> bool {
+    inner.left() >= outer.left()
+        && inner.right() <= outer.right()
+        && inner.top() >= outer.top()
+        && inner.bottom() <= outer.bottom()
+}
+
+/// `angle` between -PI and PI.
+fn wrap_angle(angle: f32) -> f32 {
+    use std::f32::consts::PI;
+
+    let angle = angle % (2. * PI);
+    if angle > PI {
+        angle - 2. * PI
+    } else if angle < -PI {
+        angle + 2. * PI
+    } else {
+        angle
+    }
+}
+
+/// Plays `world` with `bots` driving every tank until at most one is left or
+/// `max_steps` steps of `delta` seconds have run. Returns the last tank
+/// standing, if any.
+pub fn play(world: &mut World, bots: &mut [Bot], delta: f32, max_steps: u32) -> Option<Player> {
+    for _ in 0..max_steps {
+        if world.tanks.len() <= 1 {
+            break;
+        }
+        let inputs: Vec<_> = bots
+            .iter_mut()
+            .map(|bot| (bot.player, bot.input(world, delta)))
+            .collect();
+        world.step(&inputs, delta);
+    }
+
+    match world.tanks.as_slice() {
+        [winner] => Some(winner.player),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::float_eq;
+
+    use super::*;
+    use crate::map::{Tile, TileMap};
+    use crate::network::INPUT_DELTA;
+
+    #[test]
+    fn difficulty_from_its_name() {
+        assert_eq!("hard".parse::<Difficulty>().unwrap(), Difficulty::Hard);
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+
+    #[test]
+    fn idles_without_an_enemy() {
+        let mut world = world(&[(Player::P1, [600., 450.])]);
+        let mut bot = Bot::new(Player::P1, Difficulty::Hard);
+
+        assert_eq!(bot.input(&world, INPUT_DELTA), PlayerInput::default());
+        world.tanks.clear();
+        assert_eq!(bot.input(&world, INPUT_DELTA), PlayerInput::default());
+    }
+
+    #[test]
+    fn targets_the_nearest_enemy_until_another_is_much_closer() {
+        let mut world = world(&[
+            (Player::P1, [600., 450.]),
+            (Player::P2, [200., 450.]),
+            (Player::P3, [600., 20.]),
+        ]);
+        let mut bot = Bot::new(Player::P1, Difficulty::Normal);
+
+        bot.input(&world, INPUT_DELTA);
+        assert_eq!(bot.target(), Some(Player::P2));
+
+        world.tanks[2].position = na::Point2::from([600., 150.]);
+        bot.input(&world, INPUT_DELTA);
+        assert_eq!(bot.target(), Some(Player::P2));
+
+        world.tanks[2].position = na::Point2::from([600., 250.]);
+        bot.input(&world, INPUT_DELTA);
+        assert_eq!(bot.target(), Some(Player::P3));
+    }
+
+    #[test]
+    fn lead_meets_the_target_with_a_bullet() {
+        let shooter = na::Point2::from([0., 0.]);
+        let target = na::Point2::from([300., 0.]);
+        let velocity = na::Vector2::from([0., 60.]);
+
+        let aim = lead(shooter, target, velocity);
+        let time = (aim - shooter).norm() / BULLET_SPEED;
+
+        assert!(float_eq!(aim.x, 300., abs <= 0.001));
+        assert!(aim.y > 0.);
+        assert!(float_eq!(aim.y, (target + velocity * time).y, abs <= 0.01));
+        assert_eq!(lead(shooter, target, na::Vector2::from([0., 0.])), target);
+    }
+
+    #[test]
+    fn hard_bots_lead_moving_targets() {
+        let mut world = world(&[(Player::P1, [600., 450.]), (Player::P2, [300., 450.])]);
+        let mut bot = Bot::new(Player::P1, Difficulty::Hard);
+        bot.input(&world, INPUT_DELTA);
+        world.tanks[1].position.y += 1.;
+
+        let aim = bot.input(&world, INPUT_DELTA).aim;
+        let aim = na::Point2::from(aim);
+
+        assert!(aim.y > world.tanks[1].position.y);
+    }
+
+    #[test]
+    fn fires_only_within_range() {
+        let world = world(&[(Player::P1, [1000., 450.]), (Player::P2, [150., 450.])]);
+        let mut easy = Bot::new(Player::P1, Difficulty::Easy);
+        let mut hard = Bot::new(Player::P1, Difficulty::Hard);
+
+        assert!(!easy.input(&world, INPUT_DELTA).fire);
+        assert!(hard.input(&world, INPUT_DELTA).fire);
+    }
+
+    #[test]
+    fn easy_bots_hold_fire_between_shots() {
+        let world = world(&[(Player::P1, [600., 450.]), (Player::P2, [300., 450.])]);
+        let mut bot = Bot::new(Player::P1, Difficulty::Easy);
+
+        let shots = (0..crate::network::TICK_RATE)
+            .filter(|_| bot.input(&world, INPUT_DELTA).fire)
+            .count();
+
+        assert_eq!(shots, 1);
+    }
+
+    #[test]
+    fn turns_towards_and_drives_at_a_distant_target() {
+        // tanks start facing left
+        let world = world(&[(Player::P1, [600., 450.]), (Player::P2, [100., 450.])]);
+        let mut bot = Bot::new(Player::P1, Difficulty::Normal);
+
+        let input = bot.input(&world, INPUT_DELTA);
+        assert!(input.forward && !input.turn_left && !input.turn_right);
+
+        let world = self::world(&[(Player::P1, [600., 450.]), (Player::P2, [600., 50.])]);
+        let input = bot.input(&world, INPUT_DELTA);
+        assert!(input.turn_right && !input.forward);
+    }
+
+    #[test]
+    fn backs_off_a_target_too_close() {
+        let world = world(&[(Player::P1, [600., 450.]), (Player::P2, [400., 450.])]);
+        let mut bot = Bot::new(Player::P1, Difficulty::Normal);
+
+        assert!(bot.input(&world, INPUT_DELTA).backward);
+    }
+
+    #[test]
+    fn turns_away_instead_of_backing_into_a_tank() {
+        // P1 backs off P2 in front of it, but P3 is right behind
+        let world = world(&[
+            (Player::P1, [600., 450.]),
+            (Player::P2, [440., 450.]),
+            (Player::P3, [770., 450.]),
+        ]);
+        let mut bot = Bot::new(Player::P1, Difficulty::Normal);
+
+        let input = bot.input(&world, INPUT_DELTA);
+        assert_eq!(bot.target(), Some(Player::P2));
+        assert!(!input.backward);
+        let input = bot.input(&world, INPUT_DELTA);
+        assert!((input.turn_left || input.turn_right) && !input.forward);
+    }
+
+    #[test]
+    fn turns_away_instead_of_driving_into_a_wall() {
+        let mut world = world(&[(Player::P1, [600., 450.]), (Player::P2, [100., 450.])]);
+        world.map = TileMap::new(24, 18);
+        world.map.set_tile(9, 9, Tile::Wall);
+        let mut bot = Bot::new(Player::P1, Difficulty::Normal);
+
+        assert!(!bot.input(&world, INPUT_DELTA).forward);
+        world.map.set_tile(9, 9, Tile::Cover);
+        let mut bot = Bot::new(Player::P1, Difficulty::Normal);
+        assert!(bot.input(&world, INPUT_DELTA).forward);
+    }
+
+    #[test]
+    fn holds_fire_behind_walls() {
+        let mut world = world(&[(Player::P1, [600., 450.]), (Player::P2, [300., 450.])]);
+        world.map = TileMap::new(24, 18);
+        world.map.set_tile(9, 9, Tile::Water);
+        let mut bot = Bot::new(Player::P1, Difficulty::Hard);
+        assert!(bot.input(&world, INPUT_DELTA).fire);
+
+        world.map.set_tile(9, 9, Tile::Wall);
+        let mut bot = Bot::new(Player::P1, Difficulty::Hard);
+        assert!(!bot.input(&world, INPUT_DELTA).fire);
+    }
+
+    #[test]
+    fn drives_round_a_wall_to_its_target() {
+        let mut world = world(&[(Player::P1, [1050., 450.]), (Player::P2, [150., 450.])]);
+        world.map = TileMap::new(24, 18);
+        for row in 7..11 {
+            world.map.set_tile(18, row, Tile::Wall);
+        }
+        let mut bot = Bot::new(Player::P1, Difficulty::Normal);
+
+        for _ in 0..(20 * crate::network::TICK_RATE) {
+            let input = bot.input(&world, INPUT_DELTA);
+            world.apply_input(Player::P1, &input, INPUT_DELTA);
+        }
+
+        assert!(world.tank(Player::P1).unwrap().position.x < 800.);
+    }
+
+    #[test]
+    fn stays_inside_the_arena() {
+        // backing off P2 would take P1 over the right edge
+        let world = world(&[(Player::P1, [1100., 450.]), (Player::P2, [900., 450.])]);
+        let mut bot = Bot::new(Player::P1, Difficulty::Normal);
+
+        for _ in 0..10 {
+            assert!(!bot.input(&world, INPUT_DELTA).backward);
+        }
+    }
+
+    #[test]
+    fn bot_matches_play_out_headlessly() {
+        let mut world = World::new(&[Player::P1, Player::P2], arena());
+        let mut bots = [
+            Bot::new(Player::P1, Difficulty::Hard),
+            Bot::new(Player::P2, Difficulty::Easy),
+        ];
+
+        let winner = play(
+            &mut world,
+            &mut bots,
+            INPUT_DELTA,
+            120 * crate::network::TICK_RATE,
+        );
+
+        assert_eq!(winner, Some(Player::P1));
+    }
+
+    fn world(tanks: &[(Player, [f32; 2])]) -> World {
+        World {
+            tanks: tanks
+                .iter()
+                .map(|(player, position)| Tank::headless(*player, na::Point2::from(*position)))
+                .collect(),
+            bullets: Vec::new(),
+            coordinate: arena(),
+            map: TileMap::default(),
+        }
+    }
+
+    fn arena() -> Rect {
+        Rect::new(0., 0., 1200., 900.)
+    }
+}
@@ -0,0 +1,187 @@
+use ggez::graphics::{self, Color, DrawMode, Rect};
+use ggez::nalgebra as na;
+use ggez::{Context, GameResult};
+
+/// The part of the world the window shows. Tanks, bullets and the map live in
+/// world coordinates, which can be larger than the window; drawing sets
+/// ggez's screen coordinates so the window shows just `view`.
+///
+/// The view is always the same size in world units, however big the window.
+/// It is scaled up or down to fit the window and centred, with black bars
+/// filling the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub view: Rect,
+    /// Size of the window in pixels.
+    pub window: na::Vector2<f32>,
+}
+
+impl Camera {
+    /// A `width` by `height` view of the world's top left corner, in a window
+    /// of the same size.
+    pub fn new(width: f32, height: f32) -> Camera {
+        Camera {
+            view: Rect::new(0., 0., width, height),
+            window: na::Vector2::from([width, height]),
+        }
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.window = na::Vector2::from([width, height]);
+    }
+
+    /// Window pixels per world unit.
+    pub fn scale(&self) -> f32 {
+        (self.window.x / self.view.w).min(self.window.y / self.view.h)
+    }
+
+    /// Screen coordinates for ggez that put `view` in the middle of the
+    /// window with the bars outside it.
+    pub fn screen_coordinates(&self) -> Rect {
+        let scale = self.scale();
+        let bars = self.bars();
+        Rect::new(
+            self.view.x - bars.x / scale,
+            self.view.y - bars.y / scale,
+            self.window.x / scale,
+            self.window.y / scale,
+        )
+    }
+
+    /// Clears the window, points ggez at the view and fills it with
+    /// `background`, leaving the bars black.
+    pub fn begin_frame(&self, ctx: &mut Context, background: Color) -> GameResult {
+        graphics::clear(ctx, graphics::BLACK);
+        graphics::set_screen_coordinates(ctx, self.screen_coordinates())?;
+        let ground = graphics::Mesh::new_rectangle(ctx, DrawMode::fill(), self.view, background)?;
+        graphics::draw(ctx, &ground, graphics::DrawParam::new())
+    }
+
+    /// Centres the view on `target` without showing anything outside
+    /// `bounds`. A world narrower or shorter than the view sits in its middle.
+    pub fn follow(&mut self, target: na::Point2<f32>, bounds: Rect) {
+        self.view.x = clamp_axis(target.x - self.view.w / 2., bounds.x, bounds.w, self.view.w);
+        self.view.y = clamp_axis(target.y - self.view.h / 2., bounds.y, bounds.h, self.view.h);
+    }
+
+    /// Where `point` in the window, e.g. the mouse, is in the world.
+    pub fn screen_to_world(&self, point: na::Point2<f32>) -> na::Point2<f32> {
+        let (scale, bars) = (self.scale(), self.bars());
+        na::Point2::from([
+            self.view.x + (point.x - bars.x) / scale,
+            self.view.y + (point.y - bars.y) / scale,
+        ])
+    }
+
+    /// Where `point` in the world shows in the window.
+    pub fn world_to_screen(&self, point: na::Point2<f32>) -> na::Point2<f32> {
+        let (scale, bars) = (self.scale(), self.bars());
+        na::Point2::from([
+            (point.x - self.view.x) * scale + bars.x,
+            (point.y - self.view.y) * scale + bars.y,
+        ])
+    }
+
+    /// Width of the bars left and right and height of those above and below
+    /// the view, in pixels.
+    fn bars(&self) -> na::Vector2<f32> {
+        (self.window - na::Vector2::from([self.view.w, self.view.h]) * self.scale()) / 2.
+    }
+}
+
+/// Start of a `view` long stretch beginning near `start` that stays within
+/// `min..min + length`.
+fn clamp_axis(start: f32, min: f32, length: f32, view: f32) -> f32 {
+    if length <= view {
+        min + (length - view) / 2.
+    } else {
+        start.max(min).min(min + length - view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_its_target_in_the_middle_of_the_view() {
+        let mut camera = Camera::new(1200., 900.);
+
+        camera.follow(na::Point2::from([1000., 800.]), world());
+
+        assert_eq!(camera.view, Rect::new(400., 350., 1200., 900.));
+    }
+
+    #[test]
+    fn stops_at_the_edges_of_the_world() {
+        let mut camera = Camera::new(1200., 900.);
+
+        camera.follow(na::Point2::from([100., 1500.]), world());
+        assert_eq!(camera.view, Rect::new(0., 900., 1200., 900.));
+        camera.follow(na::Point2::from([2400., -50.]), world());
+        assert_eq!(camera.view, Rect::new(800., 0., 1200., 900.));
+    }
+
+    #[test]
+    fn centres_worlds_smaller_than_the_view() {
+        let mut camera = Camera::new(1200., 900.);
+
+        camera.follow(na::Point2::from([0., 0.]), Rect::new(0., 0., 1000., 2000.));
+
+        assert_eq!(camera.view, Rect::new(-100., 0., 1200., 900.));
+    }
+
+    #[test]
+    fn converts_between_window_and_world() {
+        let mut camera = Camera::new(1200., 900.);
+        camera.follow(na::Point2::from([1000., 800.]), world());
+
+        let world_point = camera.screen_to_world(na::Point2::from([10., 20.]));
+
+        assert_eq!(world_point, na::Point2::from([410., 370.]));
+        assert_eq!(
+            camera.world_to_screen(world_point),
+            na::Point2::from([10., 20.])
+        );
+    }
+
+    #[test]
+    fn wide_windows_get_bars_at_the_sides() {
+        let mut camera = Camera::new(1200., 900.);
+        camera.resize(1800., 450.);
+
+        assert_eq!(camera.scale(), 0.5);
+        assert_eq!(
+            camera.screen_coordinates(),
+            Rect::new(-1200., 0., 3600., 900.)
+        );
+        assert_eq!(
+            camera.screen_to_world(na::Point2::from([600., 0.])),
+            na::Point2::from([0., 0.])
+        );
+        assert_eq!(
+            camera.screen_to_world(na::Point2::from([1200., 450.])),
+            na::Point2::from([1200., 900.])
+        );
+    }
+
+    #[test]
+    fn tall_windows_get_bars_above_and_below() {
+        let mut camera = Camera::new(1200., 900.);
+        camera.follow(na::Point2::from([1000., 800.]), world());
+        camera.resize(2400., 2400.);
+
+        assert_eq!(camera.scale(), 2.);
+        assert_eq!(
+            camera.screen_coordinates(),
+            Rect::new(400., 200., 1200., 1200.)
+        );
+        let point = na::Point2::from([410., 370.]);
+        assert_eq!(camera.world_to_screen(point), na::Point2::from([20., 340.]));
+        assert_eq!(camera.screen_to_world(na::Point2::from([20., 340.])), point);
+    }
+
+    fn world() -> Rect {
+        Rect::new(0., 0., 2000., 1800.)
+    }
+}
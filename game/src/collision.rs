@@ -0,0 +1,213 @@
+use ggez::graphics::Rect;
+use ggez::nalgebra as na;
+
+/// An oriented bounding box: a rectangle centred on `center` and rotated by
+/// `rotation` radians, the way tanks are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obb {
+    pub center: na::Point2<f32>,
+    pub half_extents: na::Vector2<f32>,
+    pub rotation: f32,
+}
+
+impl Obb {
+    pub fn new(center: na::Point2<f32>, width: f32, height: f32, rotation: f32) -> Self {
+        Obb {
+            center,
+            half_extents: na::Vector2::from([width / 2., height / 2.]),
+            rotation,
+        }
+    }
+
+    /// The unrotated box covering `rect`, e.g. a wall or the edge of the map.
+    pub fn from_rect(rect: Rect) -> Self {
+        Obb::new(
+            na::Point2::from([rect.x + rect.w / 2., rect.y + rect.h / 2.]),
+            rect.w,
+            rect.h,
+            0.,
+        )
+    }
+
+    /// Unit vectors along the box's width and height.
+    pub fn axes(&self) -> [na::Vector2<f32>; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        [
+            na::Vector2::from([cos, sin]),
+            na::Vector2::from([-sin, cos]),
+        ]
+    }
+
+    pub fn corners(&self) -> [na::Point2<f32>; 4] {
+        let [x, y] = self.axes();
+        let x = x * self.half_extents.x;
+        let y = y * self.half_extents.y;
+        [
+            self.center - x - y,
+            self.center + x - y,
+            self.center + x + y,
+            self.center - x + y,
+        ]
+    }
+
+    pub fn contains(&self, point: na::Point2<f32>) -> bool {
+        let offset = point - self.center;
+        let [x, y] = self.axes();
+
+        offset.dot(&x).abs() <= self.half_extents.x && offset.dot(&y).abs() <= self.half_extents.y
+    }
+
+    /// Separating axis test. Boxes that only touch along an edge don't overlap,
+    /// so tanks can park right next to each other.
+    pub fn overlaps(&self, other: &Obb) -> bool {
+        let [a, b] = self.axes();
+        let [c, d] = other.axes();
+
+        [a, b, c, d].iter().all(|axis| {
+            let (min, max) = self.project(axis);
+            let (other_min, other_max) = other.project(axis);
+            min < other_max && other_min < max
+        })
+    }
+
+    /// Smallest axis-aligned rectangle around the box.
+    pub fn bounding_rect(&self) -> Rect {
+        let corners = self.corners();
+        let (mut left, mut top) = (corners[0].x, corners[0].y);
+        let (mut right, mut bottom) = (left, top);
+        for corner in &corners[1..] {
+            left = left.min(corner.x);
+            right = right.max(corner.x);
+            top = top.min(corner.y);
+            bottom = bottom.max(corner.y);
+        }
+        Rect::new(left, top, right - left, bottom - top)
+    }
+
+    /// Interval covered by the box along `axis`.
+    fn project(&self, axis: &na::Vector2<f32>) -> (f32, f32) {
+        let [x, y] = self.axes();
+        let center = self.center.coords.dot(axis);
+        let radius =
+            self.half_extents.x * x.dot(axis).abs() + self.half_extents.y * y.dot(axis).abs();
+        (center - radius, center + radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    use float_eq::float_eq;
+
+    use super::*;
+
+    #[test]
+    fn unrotated_boxes_overlap_like_rects() {
+        let a = Obb::new(point(0., 0.), 10., 10., 0.);
+
+        assert!(a.overlaps(&Obb::new(point(9., 9.), 10., 10., 0.)));
+        assert!(!a.overlaps(&Obb::new(point(11., 0.), 10., 10., 0.)));
+        assert!(!a.overlaps(&Obb::new(point(0., -11.), 10., 10., 0.)));
+    }
+
+    #[test]
+    fn touching_boxes_dont_overlap() {
+        let a = Obb::new(point(0., 0.), 10., 10., 0.);
+
+        assert!(!a.overlaps(&Obb::new(point(10., 0.), 10., 10., 0.)));
+    }
+
+    #[test]
+    fn rotated_box_misses_what_its_bounding_rect_hits() {
+        // a long thin box at 45 degrees: its corner region is empty
+        let diagonal = Obb::new(point(0., 0.), 100., 10., FRAC_PI_4);
+        let corner = Obb::new(point(30., -30.), 10., 10., 0.);
+
+        assert!(diagonal.bounding_rect().overlaps(&corner.bounding_rect()));
+        assert!(!diagonal.overlaps(&corner));
+        assert!(diagonal.overlaps(&Obb::new(point(30., 30.), 10., 10., 0.)));
+    }
+
+    #[test]
+    fn overlap_is_symmetric_at_every_angle() {
+        let fixed = Obb::new(point(0., 0.), 146., 100., 0.3);
+        for step in 0..72 {
+            let angle = step as f32 * 2. * PI / 72.;
+            for distance in &[50., 100., 130., 200.] {
+                let (sin, cos) = angle.sin_cos();
+                let moving = Obb::new(point(distance * cos, distance * sin), 146., 100., angle);
+
+                assert_eq!(
+                    fixed.overlaps(&moving),
+                    moving.overlaps(&fixed),
+                    "angle {} distance {}",
+                    angle,
+                    distance
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn boxes_side_by_side_along_their_length_at_any_angle() {
+        for step in 0..36 {
+            let angle = step as f32 * 2. * PI / 36.;
+            let (sin, cos) = angle.sin_cos();
+            // the height axis points along (-sin, cos)
+            let side = |gap: f32| point(-(100. + gap) * sin, (100. + gap) * cos);
+            let a = Obb::new(point(0., 0.), 146., 100., angle);
+
+            assert!(
+                a.overlaps(&Obb::new(side(-1.), 146., 100., angle)),
+                "angle {}",
+                angle
+            );
+            assert!(
+                !a.overlaps(&Obb::new(side(1.), 146., 100., angle)),
+                "angle {}",
+                angle
+            );
+        }
+    }
+
+    #[test]
+    fn crossed_boxes_overlap() {
+        let a = Obb::new(point(0., 0.), 100., 10., 0.);
+        let b = Obb::new(point(0., 0.), 100., 10., FRAC_PI_2);
+
+        assert!(a.overlaps(&b));
+    }
+
+    #[test]
+    fn contains_points_inside_the_rotated_box() {
+        let obb = Obb::new(point(10., 10.), 20., 4., FRAC_PI_2);
+
+        assert!(obb.contains(point(10., 19.)));
+        assert!(obb.contains(point(11., 1.)));
+        assert!(!obb.contains(point(19., 10.)));
+    }
+
+    #[test]
+    fn from_rect_covers_the_rect() {
+        let obb = Obb::from_rect(Rect::new(10., 20., 30., 40.));
+
+        assert_eq!(obb.center, point(25., 40.));
+        assert_eq!(obb.bounding_rect(), Rect::new(10., 20., 30., 40.));
+    }
+
+    #[test]
+    fn corners_follow_the_rotation() {
+        let obb = Obb::new(point(0., 0.), 20., 10., FRAC_PI_2);
+        let corners = obb.corners();
+
+        assert!(float_eq!(corners[0].x, 5., abs <= 0.0001));
+        assert!(float_eq!(corners[0].y, -10., abs <= 0.0001));
+        assert!(float_eq!(corners[2].x, -5., abs <= 0.0001));
+        assert!(float_eq!(corners[2].y, 10., abs <= 0.0001));
+    }
+
+    fn point(x: f32, y: f32) -> na::Point2<f32> {
+        na::Point2::from([x, y])
+    }
+}
@@ -0,0 +1,495 @@
+use std::fs;
+use std::path::PathBuf;
+
+use ggez::event::{self, KeyCode, KeyMods, MouseButton};
+use ggez::graphics::{self, Color, DrawMode, MeshBuilder};
+use ggez::nalgebra as na;
+use ggez::{Context, GameResult};
+
+use crate::camera::Camera;
+use crate::map::{Map, Pickup, PickupKind, Tile, TileMap, TILE_SIZE};
+use crate::objects::Tank;
+use crate::state::{spawn_positions, Player, BACKGROUND, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+/// How close to a pickup a right click has to be to remove it.
+const PICKUP_RADIUS: f32 = 15.;
+/// Undo steps kept, older ones are dropped.
+const UNDO_LIMIT: usize = 100;
+/// How far the arrow keys scroll maps larger than the window.
+const SCROLL_STEP: f32 = 4. * TILE_SIZE;
+
+/// What a left click does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Paint(Tile),
+    Spawn(Player),
+    Pickup(PickupKind),
+}
+
+impl Tool {
+    /// 1 to 4 paint floor, wall, water and cover, 5 to 8 move P1 to P4's
+    /// spawn, 9 and 0 place health and ammo pickups.
+    pub fn for_key(key: KeyCode) -> Option<Tool> {
+        let tool = match key {
+            KeyCode::Key1 => Tool::Paint(Tile::Floor),
+            KeyCode::Key2 => Tool::Paint(Tile::Wall),
+            KeyCode::Key3 => Tool::Paint(Tile::Water),
+            KeyCode::Key4 => Tool::Paint(Tile::Cover),
+            KeyCode::Key5 => Tool::Spawn(Player::P1),
+            KeyCode::Key6 => Tool::Spawn(Player::P2),
+            KeyCode::Key7 => Tool::Spawn(Player::P3),
+            KeyCode::Key8 => Tool::Spawn(Player::P4),
+            KeyCode::Key9 => Tool::Pickup(PickupKind::Health),
+            KeyCode::Key0 => Tool::Pickup(PickupKind::Ammo),
+            _ => return None,
+        };
+        Some(tool)
+    }
+
+    fn name(self) -> String {
+        match self {
+            Tool::Paint(tile) => format!("{:?}", tile).to_lowercase(),
+            Tool::Spawn(player) => format!("{:?} spawn", player),
+            Tool::Pickup(kind) => format!("{} pickup", kind.name()),
+        }
+    }
+}
+
+/// A map being edited and the history of changes to it. A stroke, everything
+/// from pressing the mouse to releasing it, is one undo step.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    pub map: Map,
+    pub tool: Tool,
+    undo: Vec<Map>,
+    redo: Vec<Map>,
+    stroke: bool,
+}
+
+impl Editor {
+    pub fn new(map: Map) -> Editor {
+        Editor {
+            map,
+            tool: Tool::Paint(Tile::Wall),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            stroke: false,
+        }
+    }
+
+    /// `columns` by `rows` tiles of floor with the players spread around them.
+    pub fn blank(columns: usize, rows: usize) -> Editor {
+        let tiles = TileMap::new(columns, rows);
+        let spawns = Player::ALL
+            .iter()
+            .copied()
+            .zip(spawn_positions(Player::ALL.len(), tiles.bounds()))
+            .collect();
+        Editor::new(Map {
+            tiles,
+            spawns,
+            pickups: Vec::new(),
+        })
+    }
+
+    /// Starts a stroke with the current tool at `point`.
+    pub fn press(&mut self, point: na::Point2<f32>) {
+        self.checkpoint();
+        self.stroke = true;
+        self.apply(point);
+    }
+
+    /// Continues the stroke, if there is one, at `point`. Only painting
+    /// follows the mouse, spawns and pickups go where the stroke started.
+    pub fn drag(&mut self, point: na::Point2<f32>) {
+        if let (true, Tool::Paint(_)) = (self.stroke, self.tool) {
+            self.apply(point);
+        }
+    }
+
+    pub fn release(&mut self) {
+        if self.stroke {
+            self.stroke = false;
+            self.discard_unchanged();
+        }
+    }
+
+    /// Removes the pickup nearest `point`, if any is within `PICKUP_RADIUS`.
+    pub fn remove_pickup(&mut self, point: na::Point2<f32>) {
+        let nearest = self
+            .map
+            .pickups
+            .iter()
+            .map(|pickup| (pickup.position - point).norm())
+            .enumerate()
+            .filter(|(_, distance)| *distance <= PICKUP_RADIUS)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+        if let Some((index, _)) = nearest {
+            self.checkpoint();
+            self.map.pickups.remove(index);
+        }
+    }
+
+    /// Steps back one change. Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(map) => {
+                self.redo.push(std::mem::replace(&mut self.map, map));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapplies the last undone change. Returns `false` if there was none.
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(map) => {
+                self.undo.push(std::mem::replace(&mut self.map, map));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The map as a map file, or why the game wouldn't load it.
+    pub fn save(&self) -> GameResult<String> {
+        let text = self.map.to_string();
+        text.parse::<Map>()?;
+        Ok(text)
+    }
+
+    fn apply(&mut self, point: na::Point2<f32>) {
+        if !self.map.tiles.bounds().contains(point) {
+            return;
+        }
+        match self.tool {
+            Tool::Paint(tile) => self.map.tiles.set_tile(
+                (point.x / TILE_SIZE) as usize,
+                (point.y / TILE_SIZE) as usize,
+                tile,
+            ),
+            Tool::Spawn(player) => {
+                self.map
+                    .spawns
+                    .retain(|(spawn_player, _)| *spawn_player != player);
+                self.map.spawns.push((player, point));
+                self.map.spawns.sort_by_key(|(player, _)| *player);
+            }
+            Tool::Pickup(kind) => self.map.pickups.push(Pickup {
+                kind,
+                position: point,
+            }),
+        }
+    }
+
+    fn checkpoint(&mut self) {
+        self.undo.push(self.map.clone());
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    fn discard_unchanged(&mut self) {
+        if self.undo.last() == Some(&self.map) {
+            self.undo.pop();
+        }
+    }
+}
+
+/// The editor's window: paints the map the way the game does, with a tank at
+/// every spawn, and saves it to `path`.
+pub struct EditorState {
+    pub editor: Editor,
+    pub path: PathBuf,
+    pub camera: Camera,
+    tanks: Vec<Tank>,
+    status: String,
+}
+
+impl EditorState {
+    /// Edits the map file at `path`, or a blank `columns` by `rows` map if
+    /// there is none yet.
+    pub fn new(ctx: &mut Context, path: PathBuf, columns: usize, rows: usize) -> GameResult<Self> {
+        let (editor, status) = if path.exists() {
+            let map = fs::read_to_string(&path)?.parse()?;
+            (Editor::new(map), format!("editing {}", path.display()))
+        } else {
+            (
+                Editor::blank(columns, rows),
+                format!("new map, Ctrl+S saves it to {}", path.display()),
+            )
+        };
+        let tanks = Player::ALL
+            .iter()
+            .map(|player| Tank::new(ctx, *player, na::Point2::origin()))
+            .collect::<GameResult<_>>()?;
+
+        Ok(EditorState {
+            editor,
+            path,
+            camera: Camera::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT),
+            tanks,
+            status,
+        })
+    }
+
+    /// Moves the view by `x`, `y` as far as the map goes.
+    fn scroll(&mut self, x: f32, y: f32) {
+        let view = self.camera.view;
+        let centre = na::Point2::from([view.x + view.w / 2. + x, view.y + view.h / 2. + y]);
+        self.camera.follow(centre, self.editor.map.tiles.bounds());
+    }
+
+    fn save(&mut self) {
+        self.status = match self
+            .editor
+            .save()
+            .and_then(|text| Ok(fs::write(&self.path, text)?))
+        {
+            Ok(()) => format!("saved {}", self.path.display()),
+            Err(e) => format!("not saved: {}", e),
+        };
+    }
+
+    fn reload(&mut self) {
+        let map = fs::read_to_string(&self.path)
+            .map_err(ggez::GameError::from)
+            .and_then(|text| text.parse::<Map>());
+        self.status = match map {
+            Ok(map) => {
+                self.editor = Editor::new(map);
+                format!("reloaded {}", self.path.display())
+            }
+            Err(e) => format!("not reloaded: {}", e),
+        };
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{} | {} | 1-4 tiles, 5-8 spawns, 9/0 pickups, right click removes a pickup, \
+             arrows scroll, Ctrl+Z/Y undo/redo, Ctrl+S save, Ctrl+O reload",
+            self.status,
+            self.editor.tool.name()
+        )
+    }
+}
+
+impl event::EventHandler for EditorState {
+    fn update(&mut self, _: &mut Context) -> GameResult {
+        Ok(())
+    }
+
+    fn mouse_button_down_event(&mut self, _: &mut Context, button: MouseButton, x: f32, y: f32) {
+        match button {
+            MouseButton::Left => self
+                .editor
+                .press(self.camera.screen_to_world(na::Point2::from([x, y]))),
+            MouseButton::Right => self
+                .editor
+                .remove_pickup(self.camera.screen_to_world(na::Point2::from([x, y]))),
+            _ => {}
+        }
+    }
+
+    fn mouse_motion_event(&mut self, _: &mut Context, x: f32, y: f32, _: f32, _: f32) {
+        self.editor
+            .drag(self.camera.screen_to_world(na::Point2::from([x, y])));
+    }
+
+    fn mouse_button_up_event(&mut self, _: &mut Context, button: MouseButton, _: f32, _: f32) {
+        if button == MouseButton::Left {
+            self.editor.release();
+        }
+    }
+
+    fn resize_event(&mut self, _: &mut Context, width: f32, height: f32) {
+        self.camera.resize(width, height);
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, mods: KeyMods, _: bool) {
+        if mods.contains(KeyMods::CTRL) {
+            match keycode {
+                KeyCode::Z => {
+                    self.editor.undo();
+                }
+                KeyCode::Y => {
+                    self.editor.redo();
+                }
+                KeyCode::S => self.save(),
+                KeyCode::O => self.reload(),
+                _ => {}
+            }
+            return;
+        }
+        match keycode {
+            KeyCode::Escape => event::quit(ctx),
+            KeyCode::Left => self.scroll(-SCROLL_STEP, 0.),
+            KeyCode::Right => self.scroll(SCROLL_STEP, 0.),
+            KeyCode::Up => self.scroll(0., -SCROLL_STEP),
+            KeyCode::Down => self.scroll(0., SCROLL_STEP),
+            key => {
+                if let Some(tool) = Tool::for_key(key) {
+                    self.editor.tool = tool;
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.camera.begin_frame(ctx, BACKGROUND.into())?;
+        let map = &self.editor.map;
+        map.tiles.draw(ctx)?;
+
+        if !map.pickups.is_empty() {
+            let mut builder = MeshBuilder::new();
+            for pickup in &map.pickups {
+                builder.circle(
+                    DrawMode::fill(),
+                    pickup.position,
+                    PICKUP_RADIUS,
+                    0.5,
+                    pickup_color(pickup.kind),
+                );
+            }
+            let mesh = builder.build(ctx)?;
+            graphics::draw(ctx, &mesh, graphics::DrawParam::new())?;
+        }
+
+        for tank in &mut self.tanks {
+            if let Some(position) = map.spawn(tank.player) {
+                tank.position = position;
+                tank.draw(ctx)?;
+            }
+        }
+
+        let param = graphics::DrawParam::new()
+            .dest(na::Point2::from([
+                self.camera.view.x + 10.,
+                self.camera.view.y + 10.,
+            ]))
+            .color(graphics::BLACK);
+        graphics::draw(ctx, &graphics::Text::new(self.help()), param)?;
+        graphics::present(ctx)
+    }
+}
+
+fn pickup_color(kind: PickupKind) -> Color {
+    match kind {
+        PickupKind::Health => Color::new(0.85, 0.15, 0.15, 1.),
+        PickupKind::Ammo => Color::new(0.9, 0.75, 0.1, 1.),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_keys_pick_tools() {
+        assert_eq!(Tool::for_key(KeyCode::Key2), Some(Tool::Paint(Tile::Wall)));
+        assert_eq!(Tool::for_key(KeyCode::Key7), Some(Tool::Spawn(Player::P3)));
+        assert_eq!(
+            Tool::for_key(KeyCode::Key0),
+            Some(Tool::Pickup(PickupKind::Ammo))
+        );
+        assert_eq!(Tool::for_key(KeyCode::W), None);
+    }
+
+    #[test]
+    fn blank_maps_place_every_player() {
+        let editor = Editor::blank(24, 18);
+
+        assert_eq!(editor.map.tiles.bounds().w, 1200.);
+        assert_eq!(
+            editor.map.spawn(Player::P3),
+            Some(na::Point2::from([150., 450.]))
+        );
+        assert!(editor.save().is_ok());
+    }
+
+    #[test]
+    fn a_stroke_paints_every_tile_it_crosses_and_undoes_at_once() {
+        let mut editor = Editor::blank(24, 18);
+
+        editor.press(point(25., 25.));
+        editor.drag(point(75., 25.));
+        editor.drag(point(125., 25.));
+        editor.release();
+
+        let walls: Vec<_> = (0..4)
+            .map(|column| editor.map.tiles.tile(column, 0))
+            .collect();
+        assert_eq!(walls, vec![Tile::Wall, Tile::Wall, Tile::Wall, Tile::Floor]);
+        assert!(editor.undo());
+        assert_eq!(editor.map, Editor::blank(24, 18).map);
+        assert!(!editor.undo());
+        assert!(editor.redo());
+        assert_eq!(editor.map.tiles.tile(2, 0), Tile::Wall);
+    }
+
+    #[test]
+    fn moving_the_mouse_without_a_stroke_does_nothing() {
+        let mut editor = Editor::blank(24, 18);
+
+        editor.drag(point(25., 25.));
+        editor.press(point(2000., 25.));
+        editor.release();
+
+        assert_eq!(editor.map.tiles.tile(0, 0), Tile::Floor);
+        assert!(!editor.undo());
+    }
+
+    #[test]
+    fn spawns_move_and_pickups_come_and_go() {
+        let mut editor = Editor::blank(24, 18);
+        editor.tool = Tool::Spawn(Player::P1);
+        editor.press(point(900., 300.));
+        editor.drag(point(950., 300.));
+        editor.release();
+        editor.tool = Tool::Pickup(PickupKind::Health);
+        editor.press(point(600., 450.));
+        editor.release();
+
+        assert_eq!(editor.map.spawn(Player::P1), Some(point(900., 300.)));
+        assert_eq!(editor.map.spawns.len(), 4);
+        assert_eq!(editor.map.pickups.len(), 1);
+
+        editor.remove_pickup(point(640., 450.));
+        assert_eq!(editor.map.pickups.len(), 1);
+        editor.remove_pickup(point(605., 455.));
+        assert!(editor.map.pickups.is_empty());
+        assert!(editor.undo());
+        assert_eq!(editor.map.pickups.len(), 1);
+    }
+
+    #[test]
+    fn maps_the_game_would_refuse_are_not_saved() {
+        let mut editor = Editor::blank(24, 18);
+        editor.press(point(1025., 425.));
+        editor.release();
+
+        assert!(editor.save().is_err());
+        editor.undo();
+        assert_eq!(editor.save().unwrap().parse::<Map>().unwrap(), editor.map);
+    }
+
+    #[test]
+    fn new_changes_drop_the_redo_history() {
+        let mut editor = Editor::blank(24, 18);
+        editor.press(point(25., 25.));
+        editor.release();
+        editor.undo();
+
+        editor.press(point(75., 25.));
+        editor.release();
+
+        assert!(!editor.redo());
+        assert_eq!(editor.map.tiles.tile(0, 0), Tile::Floor);
+    }
+
+    fn point(x: f32, y: f32) -> na::Point2<f32> {
+        na::Point2::from([x, y])
+    }
+}
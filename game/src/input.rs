@@ -0,0 +1,803 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+use ggez::event::{Axis, Button, GamepadId, KeyCode, MouseButton};
+use ggez::nalgebra as na;
+use ggez::{filesystem, Context, GameError, GameResult};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::network::protocol::PlayerInput;
+use crate::objects::Tank;
+use crate::state::Player;
+
+/// Where the controls are read from, inside the resources directory.
+pub const CONTROLS_PATH: &str = "/controls.toml";
+/// How far a stick has to be pushed before it counts, so a worn stick
+/// resting slightly off centre doesn't drive the tank.
+pub const STICK_DEAD_ZONE: f32 = 0.3;
+/// How far ahead of the turret a stick or the tank's heading aims, far enough
+/// that the tank moving during the step barely turns it.
+const AIM_DISTANCE: f32 = 1000.;
+
+/// Something a player can do, whatever device it comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Forward,
+    Back,
+    TurnLeft,
+    TurnRight,
+    Fire,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Forward,
+        Action::Back,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Fire,
+    ];
+}
+
+/// A physical key or button. Written in config files as the key's name
+/// (`"W"`, `"Space"`), `"Mouse"` followed by the button (`"MouseLeft"`) or
+/// `"Pad"` followed by the gamepad button (`"PadSouth"`) or by a gamepad axis
+/// and the way it is pushed (`"PadLeftStickY+"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(Button),
+    /// The axis pushed past `STICK_DEAD_ZONE` towards its positive end.
+    AxisPositive(Axis),
+    AxisNegative(Axis),
+}
+
+/// What the turret follows.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aim {
+    Mouse,
+    /// The direction the right stick is pushed. A released stick leaves the
+    /// turret where it is.
+    RightStick,
+    /// Wherever the tank is heading, for players with nothing to aim with.
+    Forward,
+}
+
+/// A gamepad event as ggez reports it, minus the `GamepadId`, so tests can
+/// feed them in by hand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    ButtonDown(Button),
+    ButtonUp(Button),
+    Axis(Axis, f32),
+}
+
+/// Buttons held and axis positions of one gamepad, built up from its events.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GamepadState {
+    pub buttons: HashSet<Button>,
+    pub axes: HashMap<Axis, f32>,
+}
+
+/// Every gamepad that sent an event, numbered in the order they were first
+/// used. `InputMap::gamepad` picks one by that number.
+#[derive(Debug, Clone, Default)]
+pub struct Gamepads {
+    ids: Vec<GamepadId>,
+    states: Vec<GamepadState>,
+}
+
+/// Everything pressed or pointed at right now, read from the `Context` or
+/// put together by hand in tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceState {
+    pub keys: HashSet<KeyCode>,
+    pub mouse_buttons: HashSet<MouseButton>,
+    /// In window coordinates from `from_context`; whoever knows the camera
+    /// moves it into the world before aiming with it.
+    pub mouse_position: na::Point2<f32>,
+    pub gamepad: GamepadState,
+}
+
+/// Which bindings trigger each `Action`. Loaded from `CONTROLS_PATH` and
+/// changeable while playing with `bind` and `unbind`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputMap {
+    pub forward: Vec<Binding>,
+    pub back: Vec<Binding>,
+    pub turn_left: Vec<Binding>,
+    pub turn_right: Vec<Binding>,
+    pub fire: Vec<Binding>,
+    pub aim: Aim,
+    /// Which of the `Gamepads` the gamepad bindings read.
+    #[serde(default)]
+    pub gamepad: usize,
+}
+
+impl Default for DeviceState {
+    fn default() -> Self {
+        DeviceState {
+            keys: HashSet::new(),
+            mouse_buttons: HashSet::new(),
+            mouse_position: na::Point2::from([0., 0.]),
+            gamepad: GamepadState::default(),
+        }
+    }
+}
+
+impl DeviceState {
+    pub fn from_context(ctx: &Context) -> Self {
+        let mouse_position = ggez::input::mouse::position(ctx);
+
+        DeviceState {
+            keys: ggez::input::keyboard::pressed_keys(ctx).clone(),
+            mouse_buttons: [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+                .iter()
+                .copied()
+                .filter(|button| ggez::input::mouse::button_pressed(ctx, *button))
+                .collect(),
+            mouse_position: na::Point2::from([mouse_position.x, mouse_position.y]),
+            ..DeviceState::default()
+        }
+    }
+
+    /// These devices with gamepad `slot`, all released if it never sent an event.
+    pub fn with_gamepad(self, gamepads: &Gamepads, slot: usize) -> Self {
+        DeviceState {
+            gamepad: gamepads.get(slot).cloned().unwrap_or_default(),
+            ..self
+        }
+    }
+
+    pub fn is_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keys.contains(&key),
+            Binding::Mouse(button) => self.mouse_buttons.contains(&button),
+            Binding::Gamepad(button) => self.gamepad.buttons.contains(&button),
+            Binding::AxisPositive(axis) => self.gamepad.axis(axis) > STICK_DEAD_ZONE,
+            Binding::AxisNegative(axis) => self.gamepad.axis(axis) < -STICK_DEAD_ZONE,
+        }
+    }
+}
+
+impl GamepadState {
+    pub fn apply(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::ButtonDown(button) => {
+                self.buttons.insert(button);
+            }
+            GamepadEvent::ButtonUp(button) => {
+                self.buttons.remove(&button);
+            }
+            GamepadEvent::Axis(axis, value) => {
+                self.axes.insert(axis, value);
+            }
+        }
+    }
+
+    /// Between -1 and 1, 0 for an axis that never moved.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.)
+    }
+
+    /// Where the right stick points on screen, or `None` while it is inside
+    /// the dead zone. Stick y grows upwards, screen y downwards.
+    pub fn right_stick(&self) -> Option<na::Vector2<f32>> {
+        let stick =
+            na::Vector2::from([self.axis(Axis::RightStickX), -self.axis(Axis::RightStickY)]);
+        if stick.norm() > STICK_DEAD_ZONE {
+            Some(stick.normalize())
+        } else {
+            None
+        }
+    }
+}
+
+impl Gamepads {
+    /// The number of gamepad `id`, handing out the next one on first use.
+    pub fn slot(&mut self, id: GamepadId) -> usize {
+        match self.ids.iter().position(|known| *known == id) {
+            Some(slot) => slot,
+            None => {
+                self.ids.push(id);
+                self.ids.len() - 1
+            }
+        }
+    }
+
+    pub fn event(&mut self, id: GamepadId, event: GamepadEvent) {
+        let slot = self.slot(id);
+        self.apply(slot, event);
+    }
+
+    pub fn apply(&mut self, slot: usize, event: GamepadEvent) {
+        if self.states.len() <= slot {
+            self.states.resize_with(slot + 1, GamepadState::default);
+        }
+        self.states[slot].apply(event);
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&GamepadState> {
+        self.states.get(slot)
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        InputMap {
+            forward: vec![Binding::Key(KeyCode::W), Binding::Key(KeyCode::Up)],
+            back: vec![Binding::Key(KeyCode::S), Binding::Key(KeyCode::Down)],
+            turn_left: vec![Binding::Key(KeyCode::A), Binding::Key(KeyCode::Left)],
+            turn_right: vec![Binding::Key(KeyCode::D), Binding::Key(KeyCode::Right)],
+            fire: vec![
+                Binding::Key(KeyCode::Space),
+                Binding::Mouse(MouseButton::Left),
+            ],
+            aim: Aim::Mouse,
+            gamepad: 0,
+        }
+    }
+}
+
+impl InputMap {
+    /// Tank controls on the left stick or d-pad, the right trigger to fire
+    /// and the right stick to aim, read from gamepad `slot`.
+    pub fn gamepad(slot: usize) -> InputMap {
+        InputMap {
+            forward: vec![
+                Binding::AxisPositive(Axis::LeftStickY),
+                Binding::Gamepad(Button::DPadUp),
+            ],
+            back: vec![
+                Binding::AxisNegative(Axis::LeftStickY),
+                Binding::Gamepad(Button::DPadDown),
+            ],
+            turn_left: vec![
+                Binding::AxisNegative(Axis::LeftStickX),
+                Binding::Gamepad(Button::DPadLeft),
+            ],
+            turn_right: vec![
+                Binding::AxisPositive(Axis::LeftStickX),
+                Binding::Gamepad(Button::DPadRight),
+            ],
+            fire: vec![
+                Binding::Gamepad(Button::RightTrigger2),
+                Binding::Gamepad(Button::RightTrigger),
+            ],
+            aim: Aim::RightStick,
+            gamepad: slot,
+        }
+    }
+
+    /// Controls for `player` sharing the machine with others: P1 on the left
+    /// of the keyboard with the mouse, P2 on the arrow keys, P3 and P4 on the
+    /// first two gamepads.
+    pub fn hot_seat(player: Player) -> InputMap {
+        match player {
+            Player::P1 => InputMap {
+                forward: vec![Binding::Key(KeyCode::W)],
+                back: vec![Binding::Key(KeyCode::S)],
+                turn_left: vec![Binding::Key(KeyCode::A)],
+                turn_right: vec![Binding::Key(KeyCode::D)],
+                ..InputMap::default()
+            },
+            Player::P2 => InputMap {
+                forward: vec![Binding::Key(KeyCode::Up)],
+                back: vec![Binding::Key(KeyCode::Down)],
+                turn_left: vec![Binding::Key(KeyCode::Left)],
+                turn_right: vec![Binding::Key(KeyCode::Right)],
+                fire: vec![
+                    Binding::Key(KeyCode::RControl),
+                    Binding::Key(KeyCode::RShift),
+                ],
+                aim: Aim::Forward,
+                gamepad: 0,
+            },
+            Player::P3 => InputMap::gamepad(0),
+            Player::P4 => InputMap::gamepad(1),
+        }
+    }
+
+    pub fn from_toml(toml: &str) -> GameResult<InputMap> {
+        toml::from_str(toml).map_err(|e| GameError::ConfigError(format!("invalid controls: {}", e)))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("controls are always valid toml")
+    }
+
+    /// Reads `path` from the resources, or the default controls if there is no such file.
+    pub fn load(ctx: &mut Context, path: &str) -> GameResult<InputMap> {
+        InputMap::load_or(ctx, path, InputMap::default())
+    }
+
+    /// Reads `path` from the resources, or `fallback` if there is no such file.
+    pub fn load_or(ctx: &mut Context, path: &str, fallback: InputMap) -> GameResult<InputMap> {
+        if !filesystem::exists(ctx, path) {
+            return Ok(fallback);
+        }
+
+        let mut toml = String::new();
+        filesystem::open(ctx, path)?.read_to_string(&mut toml)?;
+        InputMap::from_toml(&toml)
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        match action {
+            Action::Forward => &self.forward,
+            Action::Back => &self.back,
+            Action::TurnLeft => &self.turn_left,
+            Action::TurnRight => &self.turn_right,
+            Action::Fire => &self.fire,
+        }
+    }
+
+    fn bindings_mut(&mut self, action: Action) -> &mut Vec<Binding> {
+        match action {
+            Action::Forward => &mut self.forward,
+            Action::Back => &mut self.back,
+            Action::TurnLeft => &mut self.turn_left,
+            Action::TurnRight => &mut self.turn_right,
+            Action::Fire => &mut self.fire,
+        }
+    }
+
+    /// Makes `binding` trigger `action`, and nothing else. Bindings that
+    /// couldn't be saved in a controls file are refused.
+    pub fn bind(&mut self, action: Action, binding: Binding) -> GameResult {
+        if !binding.has_name() {
+            return Err(GameError::ConfigError(format!(
+                "{:?} can't be bound",
+                binding
+            )));
+        }
+
+        for other in Action::ALL.iter() {
+            self.unbind(*other, binding);
+        }
+        self.bindings_mut(action).push(binding);
+        Ok(())
+    }
+
+    pub fn unbind(&mut self, action: Action, binding: Binding) {
+        self.bindings_mut(action).retain(|bound| *bound != binding);
+    }
+
+    pub fn is_active(&self, action: Action, devices: &DeviceState) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| devices.is_pressed(*binding))
+    }
+
+    /// The input for `tank`, which stick aiming needs to know where the
+    /// turret is.
+    pub fn input(&self, devices: &DeviceState, tank: Option<&Tank>) -> PlayerInput {
+        let aim = match (self.aim, tank) {
+            (Aim::Mouse, _) => devices.mouse_position,
+            (Aim::RightStick, Some(tank)) => {
+                let direction = devices
+                    .gamepad
+                    .right_stick()
+                    .unwrap_or(tank.turret_direction);
+                tank.turret_origin() + direction * AIM_DISTANCE
+            }
+            (Aim::Forward, Some(tank)) => {
+                tank.turret_origin() + tank.tank_direction.normalize() * AIM_DISTANCE
+            }
+            (Aim::RightStick, None) | (Aim::Forward, None) => na::Point2::from([0., 0.]),
+        };
+
+        PlayerInput {
+            forward: self.is_active(Action::Forward, devices),
+            backward: self.is_active(Action::Back, devices),
+            turn_left: self.is_active(Action::TurnLeft, devices),
+            turn_right: self.is_active(Action::TurnRight, devices),
+            fire: self.is_active(Action::Fire, devices),
+            aim: [aim.x, aim.y],
+        }
+    }
+}
+
+macro_rules! names {
+    ($table:ident: $ty:ident { $($variant:ident),* $(,)? }) => {
+        const $table: &[(&str, $ty)] = &[$((stringify!($variant), $ty::$variant)),*];
+    };
+    // Also fails to compile when a variant is missing from the table.
+    ($table:ident: every $ty:ident { $($variant:ident),* $(,)? }) => {
+        names!($table: $ty { $($variant),* });
+        const _: fn($ty) = |value| match value {
+            $($ty::$variant)|* => {}
+        };
+    };
+}
+
+names!(KEYS: every KeyCode {
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24,
+    Snapshot, Scroll, Pause, Insert, Home, Delete, End, PageDown, PageUp,
+    Left, Up, Right, Down, Back, Return, Space, Tab, Compose, Caret,
+    Numlock, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8,
+    Numpad9, NumpadEnter, NumpadComma, NumpadEquals, Add, Subtract, Multiply, Divide, Decimal,
+    Apostrophe, At, Backslash, Colon, Comma, Equals, Grave, LBracket, Minus, Period, RBracket,
+    Semicolon, Slash, Underline, Yen, OEM102, AbntC1, AbntC2, Ax, Unlabeled,
+    LAlt, LControl, LShift, LWin, RAlt, RControl, RShift, RWin, Capital, Apps, Sysrq,
+    Kana, Kanji, Convert, NoConvert,
+    Calculator, Mail, MediaSelect, MediaStop, Mute, MyComputer, NavigateForward,
+    NavigateBackward, NextTrack, PlayPause, Power, PrevTrack, Sleep, Stop, VolumeDown,
+    VolumeUp, Wake, WebBack, WebFavorites, WebForward, WebHome, WebRefresh, WebSearch, WebStop,
+    Copy, Paste, Cut,
+});
+
+names!(MOUSE_BUTTONS: MouseButton { Left, Right, Middle });
+
+names!(GAMEPAD_BUTTONS: Button {
+    South, East, North, West, C, Z,
+    LeftTrigger, LeftTrigger2, RightTrigger, RightTrigger2,
+    Select, Start, Mode, LeftThumb, RightThumb,
+    DPadUp, DPadDown, DPadLeft, DPadRight,
+});
+
+names!(GAMEPAD_AXES: Axis {
+    LeftStickX, LeftStickY, LeftZ, RightStickX, RightStickY, RightZ, DPadX, DPadY,
+});
+
+fn name_of<T: PartialEq>(table: &[(&'static str, T)], value: &T) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(_, candidate)| candidate == value)
+        .map(|(name, _)| *name)
+}
+
+fn parse_name<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(candidate, _)| *candidate == name)
+        .map(|(_, value)| *value)
+}
+
+impl Binding {
+    /// Whether controls files can name it. Every key can, but not extra mouse
+    /// buttons or gamepad inputs gilrs doesn't recognise.
+    pub fn has_name(&self) -> bool {
+        match self {
+            Binding::Key(key) => name_of(KEYS, key),
+            Binding::Mouse(button) => name_of(MOUSE_BUTTONS, button),
+            Binding::Gamepad(button) => name_of(GAMEPAD_BUTTONS, button),
+            Binding::AxisPositive(axis) | Binding::AxisNegative(axis) => {
+                name_of(GAMEPAD_AXES, axis)
+            }
+        }
+        .is_some()
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (prefix, name) = match self {
+            Binding::Key(key) => ("", name_of(KEYS, key)),
+            Binding::Mouse(button) => ("Mouse", name_of(MOUSE_BUTTONS, button)),
+            Binding::Gamepad(button) => ("Pad", name_of(GAMEPAD_BUTTONS, button)),
+            Binding::AxisPositive(axis) | Binding::AxisNegative(axis) => {
+                ("Pad", name_of(GAMEPAD_AXES, axis))
+            }
+        };
+        let sign = match self {
+            Binding::AxisPositive(_) => "+",
+            Binding::AxisNegative(_) => "-",
+            _ => "",
+        };
+        match name {
+            Some(name) => write!(f, "{}{}{}", prefix, name, sign),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = GameError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let binding = if let Some(button) = name.strip_prefix("Mouse") {
+            parse_name(MOUSE_BUTTONS, button).map(Binding::Mouse)
+        } else if let Some(axis) = name.strip_prefix("Pad").and_then(|n| n.strip_suffix('+')) {
+            parse_name(GAMEPAD_AXES, axis).map(Binding::AxisPositive)
+        } else if let Some(axis) = name.strip_prefix("Pad").and_then(|n| n.strip_suffix('-')) {
+            parse_name(GAMEPAD_AXES, axis).map(Binding::AxisNegative)
+        } else if let Some(button) = name.strip_prefix("Pad") {
+            parse_name(GAMEPAD_BUTTONS, button).map(Binding::Gamepad)
+        } else {
+            parse_name(KEYS, name).map(Binding::Key)
+        };
+
+        binding.ok_or_else(|| GameError::ConfigError(format!("unknown key or button {}", name)))
+    }
+}
+
+impl Serialize for Binding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse::<Binding>().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_controls() {
+        let map = InputMap::default();
+        let devices = DeviceState {
+            keys: vec![KeyCode::Up, KeyCode::Left].into_iter().collect(),
+            mouse_buttons: vec![MouseButton::Left].into_iter().collect(),
+            mouse_position: na::Point2::from([1., 2.]),
+            ..DeviceState::default()
+        };
+
+        let input = map.input(&devices, None);
+
+        assert!(input.forward);
+        assert!(!input.backward);
+        assert!(input.turn_left);
+        assert!(!input.turn_right);
+        assert!(input.fire);
+        assert_eq!(input.aim, [1., 2.]);
+    }
+
+    #[test]
+    fn unbound_keys_do_nothing() {
+        let map = InputMap::default();
+        let devices = DeviceState {
+            keys: vec![KeyCode::K, KeyCode::V].into_iter().collect(),
+            ..DeviceState::default()
+        };
+
+        assert_eq!(
+            map.input(&devices, None),
+            PlayerInput {
+                aim: [0., 0.],
+                ..PlayerInput::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rebinding_moves_the_binding_to_the_new_action() {
+        let mut map = InputMap::default();
+        let devices = DeviceState {
+            keys: vec![KeyCode::W].into_iter().collect(),
+            ..DeviceState::default()
+        };
+
+        map.bind(Action::Fire, Binding::Key(KeyCode::W)).unwrap();
+
+        assert!(map.is_active(Action::Fire, &devices));
+        assert!(!map.is_active(Action::Forward, &devices));
+        assert_eq!(map.bindings(Action::Forward), &[Binding::Key(KeyCode::Up)]);
+    }
+
+    #[test]
+    fn unbind_removes_only_that_binding() {
+        let mut map = InputMap::default();
+
+        map.unbind(Action::Fire, Binding::Mouse(MouseButton::Left));
+
+        assert_eq!(map.bindings(Action::Fire), &[Binding::Key(KeyCode::Space)]);
+    }
+
+    #[test]
+    fn gamepad_buttons_can_be_bound() {
+        let mut map = InputMap::default();
+        map.bind(Action::Fire, Binding::Gamepad(Button::RightTrigger2))
+            .unwrap();
+        let mut devices = DeviceState::default();
+        devices
+            .gamepad
+            .apply(GamepadEvent::ButtonDown(Button::RightTrigger2));
+
+        assert!(map.input(&devices, None).fire);
+    }
+
+    #[test]
+    fn controls_round_trip_through_toml() {
+        let mut map = InputMap::default();
+        map.bind(Action::Forward, Binding::Gamepad(Button::DPadUp))
+            .unwrap();
+
+        let toml = map.to_toml();
+
+        assert!(
+            toml.contains(r#"forward = ["W", "Up", "PadDPadUp"]"#),
+            "{}",
+            toml
+        );
+        assert_eq!(InputMap::from_toml(&toml).unwrap(), map);
+    }
+
+    #[test]
+    fn controls_from_a_config_file() {
+        let map = InputMap::from_toml(
+            r#"
+            forward = ["I"]
+            back = ["K"]
+            turn_left = ["J"]
+            turn_right = ["L"]
+            fire = ["RShift", "MouseRight"]
+            aim = "Mouse"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(map.bindings(Action::TurnLeft), &[Binding::Key(KeyCode::J)]);
+        assert_eq!(
+            map.bindings(Action::Fire),
+            &[
+                Binding::Key(KeyCode::RShift),
+                Binding::Mouse(MouseButton::Right)
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_config_errors() {
+        assert!("Mouse4".parse::<Binding>().is_err());
+        assert!("Hyper".parse::<Binding>().is_err());
+        match InputMap::from_toml("forward = [\"Warp\"]") {
+            Err(GameError::ConfigError(_)) => {}
+            other => panic!("expected a config error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shipped_controls_are_the_defaults() {
+        let shipped = InputMap::from_toml(include_str!("../../resources/controls.toml")).unwrap();
+
+        assert_eq!(shipped, InputMap::default());
+    }
+
+    #[test]
+    fn sticks_drive_past_the_dead_zone() {
+        let map = InputMap::gamepad(0);
+        let mut devices = DeviceState::default();
+        devices
+            .gamepad
+            .apply(GamepadEvent::Axis(Axis::LeftStickX, -0.2));
+        devices
+            .gamepad
+            .apply(GamepadEvent::Axis(Axis::LeftStickY, 0.8));
+
+        let input = map.input(&devices, None);
+
+        assert!(input.forward);
+        assert!(!input.turn_left);
+        assert!(!input.turn_right);
+    }
+
+    #[test]
+    fn right_stick_aims_from_the_turret() {
+        let map = InputMap::gamepad(0);
+        let tank = Tank::headless(Player::P1, na::Point2::from([100., 100.]));
+        let origin = tank.turret_origin();
+        let mut devices = DeviceState::default();
+
+        // a released stick keeps the turret pointing where it was
+        let aim = map.input(&devices, Some(&tank)).aim;
+        assert_eq!(aim, [origin.x - AIM_DISTANCE, origin.y]);
+
+        devices
+            .gamepad
+            .apply(GamepadEvent::Axis(Axis::RightStickY, 1.));
+        let aim = map.input(&devices, Some(&tank)).aim;
+        assert_eq!(aim, [origin.x, origin.y - AIM_DISTANCE]);
+    }
+
+    #[test]
+    fn gamepad_events_update_their_own_slot() {
+        let mut gamepads = Gamepads::default();
+
+        gamepads.apply(1, GamepadEvent::ButtonDown(Button::South));
+        gamepads.apply(1, GamepadEvent::Axis(Axis::RightZ, 0.5));
+
+        assert_eq!(gamepads.get(0), Some(&GamepadState::default()));
+        assert!(gamepads.get(1).unwrap().buttons.contains(&Button::South));
+        assert_eq!(gamepads.get(1).unwrap().axis(Axis::RightZ), 0.5);
+        assert_eq!(gamepads.get(2), None);
+
+        gamepads.apply(1, GamepadEvent::ButtonUp(Button::South));
+        assert!(gamepads.get(1).unwrap().buttons.is_empty());
+    }
+
+    #[test]
+    fn axis_binding_names() {
+        let binding = "PadLeftStickY+".parse::<Binding>().unwrap();
+
+        assert_eq!(binding, Binding::AxisPositive(Axis::LeftStickY));
+        assert_eq!(
+            Binding::AxisNegative(Axis::DPadX).to_string(),
+            "PadDPadX-".to_owned()
+        );
+        assert!("PadLeftStickY".parse::<Binding>().is_err());
+        assert!("PadSouth+".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn every_key_round_trips_through_its_name() {
+        for (_, key) in KEYS {
+            let binding = Binding::Key(*key);
+
+            assert!(binding.has_name());
+            assert_eq!(binding.to_string().parse::<Binding>().unwrap(), binding);
+        }
+
+        let mut map = InputMap::default();
+        map.bind(Action::Fire, Binding::Key(KeyCode::Capital))
+            .unwrap();
+        assert!(map
+            .to_toml()
+            .contains(r#"fire = ["Space", "MouseLeft", "Capital"]"#));
+        assert_eq!(InputMap::from_toml(&map.to_toml()).unwrap(), map);
+    }
+
+    #[test]
+    fn bindings_without_a_name_cant_be_bound() {
+        let mut map = InputMap::default();
+
+        assert!(map
+            .bind(Action::Fire, Binding::Mouse(MouseButton::Other(4)))
+            .is_err());
+        assert!(map
+            .bind(Action::Fire, Binding::Gamepad(Button::Unknown))
+            .is_err());
+        assert!(map
+            .bind(Action::Forward, Binding::AxisPositive(Axis::Unknown))
+            .is_err());
+        assert_eq!(map, InputMap::default());
+    }
+
+    #[test]
+    fn gamepad_controls_round_trip_through_toml() {
+        let map = InputMap::gamepad(2);
+
+        assert_eq!(InputMap::from_toml(&map.to_toml()).unwrap(), map);
+    }
+
+    #[test]
+    fn hot_seat_players_share_no_controls() {
+        let p1 = InputMap::hot_seat(Player::P1);
+        let p2 = InputMap::hot_seat(Player::P2);
+
+        for action in Action::ALL.iter() {
+            for binding in p1.bindings(*action) {
+                for other in Action::ALL.iter() {
+                    assert!(!p2.bindings(*other).contains(binding), "{}", binding);
+                }
+            }
+        }
+        assert_eq!(InputMap::hot_seat(Player::P3).gamepad, 0);
+        assert_eq!(InputMap::hot_seat(Player::P4).gamepad, 1);
+    }
+
+    #[test]
+    fn forward_aim_follows_the_tank() {
+        let map = InputMap::hot_seat(Player::P2);
+        let tank = Tank::headless(Player::P2, na::Point2::from([100., 100.]));
+        let origin = tank.turret_origin();
+
+        let aim = map.input(&DeviceState::default(), Some(&tank)).aim;
+
+        assert_eq!(aim, [origin.x - AIM_DISTANCE, origin.y]);
+    }
+}
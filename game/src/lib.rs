@@ -1,2 +1,10 @@
+pub mod bot;
+pub mod camera;
+pub mod collision;
+pub mod editor;
+pub mod input;
+pub mod map;
+pub mod network;
 pub mod objects;
+pub mod simulation;
 pub mod state;
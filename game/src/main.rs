@@ -1,15 +1,50 @@
 use std::path;
 
-use ggez;
 use ggez::conf::WindowSetup;
 use ggez::event;
 
+pub mod bot;
+pub mod camera;
+pub mod collision;
+pub mod editor;
+pub mod input;
+pub mod map;
+pub mod network;
 pub mod objects;
+pub mod simulation;
 pub mod state;
 
+use network::client::{Client, ClientState};
+use network::local::LocalServer;
+use network::HOST_ADDR;
 use state::{window_state_mode, MainState};
 
 pub fn main() -> ggez::GameResult {
+    let host = std::env::args().any(|arg| arg == "--host");
+    let server = std::env::args().skip_while(|arg| arg != "--connect").nth(1);
+    let local_players = match std::env::args().skip_while(|arg| arg != "--local").nth(1) {
+        Some(count) => count.parse().map_err(|_| {
+            ggez::GameError::ConfigError(format!("--local expects a number, got {}", count))
+        })?,
+        None => 1,
+    };
+    let player_count = match std::env::args().skip_while(|arg| arg != "--players").nth(1) {
+        Some(count) => count.parse().map_err(|_| {
+            ggez::GameError::ConfigError(format!("--players expects a number, got {}", count))
+        })?,
+        None => local_players.max(2),
+    };
+    let difficulty = match std::env::args()
+        .skip_while(|arg| arg != "--difficulty")
+        .nth(1)
+    {
+        Some(name) => name.parse()?,
+        None => bot::Difficulty::Normal,
+    };
+    let map = std::env::args()
+        .skip_while(|arg| arg != "--map")
+        .nth(1)
+        .unwrap_or_else(|| map::DEFAULT_MAP.to_owned());
     let resources_dir = path::PathBuf::from("../resources");
     let cb = ggez::ContextBuilder::new("tank_battle", "naomijub")
         .add_resource_path(resources_dir)
@@ -22,7 +57,22 @@ pub fn main() -> ggez::GameResult {
         })
         .window_mode(window_state_mode());
 
+    if host {
+        let (ctx, event_loop) = &mut cb.build()?;
+        let local_server = LocalServer::with_map(HOST_ADDR, map::Map::load(ctx, &map)?)?;
+        let client = Client::connect(local_server.loopback_addr())?;
+        let mut state = ClientState::new(ctx, client)?;
+        return event::run(ctx, event_loop, &mut state);
+    }
+
+    if let Some(server) = server {
+        let client = Client::connect(server)?;
+        let (ctx, event_loop) = &mut cb.build()?;
+        let mut state = ClientState::new(ctx, client)?;
+        return event::run(ctx, event_loop, &mut state);
+    }
+
     let (ctx, event_loop) = &mut cb.build()?;
-    let mut state = MainState::new(ctx, state::Player::P1)?;
+    let mut state = MainState::new(ctx, &map, local_players, player_count, difficulty)?;
     event::run(ctx, event_loop, &mut state)
 }
@@ -0,0 +1,739 @@
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+use ggez::graphics::{self, Color, DrawMode, MeshBuilder, Rect};
+use ggez::nalgebra as na;
+use ggez::{filesystem, Context, GameError, GameResult};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::collision::Obb;
+use crate::objects::TANK_DIMENSIONS;
+use crate::state::{Player, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+/// The arena played offline, inside the resources directory.
+pub const DEFAULT_MAP: &str = "/maps/arena.map";
+/// Width and height of a tile in pixels.
+pub const TILE_SIZE: f32 = 50.;
+
+/// One square of the arena. Written in map files as a single character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    /// `.`, open ground.
+    Floor,
+    /// `#`, stops tanks and bullets.
+    Wall,
+    /// `~`, stops tanks, bullets fly over it.
+    Water,
+    /// `%`, stops bullets, tanks drive through it.
+    Cover,
+}
+
+impl Tile {
+    pub fn blocks_tanks(self) -> bool {
+        self == Tile::Wall || self == Tile::Water
+    }
+
+    pub fn blocks_bullets(self) -> bool {
+        self == Tile::Wall || self == Tile::Cover
+    }
+
+    pub fn from_char(c: char) -> Option<Tile> {
+        match c {
+            '.' => Some(Tile::Floor),
+            '#' => Some(Tile::Wall),
+            '~' => Some(Tile::Water),
+            '%' => Some(Tile::Cover),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Tile::Floor => '.',
+            Tile::Wall => '#',
+            Tile::Water => '~',
+            Tile::Cover => '%',
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Tile::Floor => Color::new(0.83, 0.69, 0.51, 1.),
+            Tile::Wall => Color::new(0.35, 0.32, 0.3, 1.),
+            Tile::Water => Color::new(0.25, 0.45, 0.75, 1.),
+            Tile::Cover => Color::new(0.3, 0.55, 0.25, 1.),
+        }
+    }
+}
+
+/// A grid of tiles with its top left corner at the origin. Anything outside
+/// the grid is floor, so an empty map leaves the arena open.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TileMap {
+    pub columns: usize,
+    pub rows: usize,
+    tiles: Vec<Tile>,
+}
+
+impl TileMap {
+    /// `columns` by `rows` tiles of floor.
+    pub fn new(columns: usize, rows: usize) -> TileMap {
+        TileMap {
+            columns,
+            rows,
+            tiles: vec![Tile::Floor; columns * rows],
+        }
+    }
+
+    /// Where a match on these tiles is played: the area they cover, or one
+    /// window's worth of open ground when there are none.
+    pub fn arena(&self) -> Rect {
+        if self.columns == 0 || self.rows == 0 {
+            Rect::new(0., 0., VIRTUAL_WIDTH, VIRTUAL_HEIGHT)
+        } else {
+            self.bounds()
+        }
+    }
+
+    /// The area the tiles cover.
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            0.,
+            0.,
+            self.columns as f32 * TILE_SIZE,
+            self.rows as f32 * TILE_SIZE,
+        )
+    }
+
+    pub fn tile(&self, column: usize, row: usize) -> Tile {
+        if column < self.columns && row < self.rows {
+            self.tiles[row * self.columns + column]
+        } else {
+            Tile::Floor
+        }
+    }
+
+    pub fn set_tile(&mut self, column: usize, row: usize, tile: Tile) {
+        if column < self.columns && row < self.rows {
+            self.tiles[row * self.columns + column] = tile;
+        }
+    }
+
+    /// The tile under `point`.
+    pub fn tile_at(&self, point: na::Point2<f32>) -> Tile {
+        if point.x < 0. || point.y < 0. {
+            return Tile::Floor;
+        }
+        self.tile(
+            (point.x / TILE_SIZE) as usize,
+            (point.y / TILE_SIZE) as usize,
+        )
+    }
+
+    pub fn tile_rect(column: usize, row: usize) -> Rect {
+        Rect::new(
+            column as f32 * TILE_SIZE,
+            row as f32 * TILE_SIZE,
+            TILE_SIZE,
+            TILE_SIZE,
+        )
+    }
+
+    /// Every tile that stops tanks, as boxes to check tank movement against.
+    pub fn tank_obstacles(&self) -> Vec<Obb> {
+        self.positions()
+            .filter(|(column, row)| self.tile(*column, *row).blocks_tanks())
+            .map(|(column, row)| Obb::from_rect(TileMap::tile_rect(column, row)))
+            .collect()
+    }
+
+    /// Whether a tank with `hitbox` would be driving into a tile it can't cross.
+    pub fn blocks_tank(&self, hitbox: &Obb) -> bool {
+        self.tank_obstacles()
+            .iter()
+            .any(|obstacle| obstacle.overlaps(hitbox))
+    }
+
+    pub fn blocks_bullet(&self, point: na::Point2<f32>) -> bool {
+        self.tile_at(point).blocks_bullets()
+    }
+
+    /// Draws every tile that isn't floor, the arena's background shows through the rest.
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        let mut builder = MeshBuilder::new();
+        let mut empty = true;
+        for (column, row) in self.positions() {
+            let tile = self.tile(column, row);
+            if tile != Tile::Floor {
+                builder.rectangle(
+                    DrawMode::fill(),
+                    TileMap::tile_rect(column, row),
+                    tile.color(),
+                );
+                empty = false;
+            }
+        }
+        if empty {
+            return Ok(());
+        }
+
+        let mesh = builder.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new())
+    }
+
+    fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+        let columns = self.columns;
+        (0..self.rows).flat_map(move |row| (0..columns).map(move |column| (column, row)))
+    }
+}
+
+/// One row of tiles per line, every row as wide as the first. Blank lines at
+/// the end are ignored.
+impl FromStr for TileMap {
+    type Err = GameError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lines = numbered_lines(text.trim_end());
+        let columns = lines.first().map_or(0, |(_, line)| line.chars().count());
+        if columns == 0 {
+            return Err(GameError::ConfigError("the map has no tiles".to_owned()));
+        }
+        parse_rows(&lines, columns)
+    }
+}
+
+impl fmt::Display for TileMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..self.rows {
+            let line: String = (0..self.columns)
+                .map(|column| self.tile(column, row).to_char())
+                .collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sent as the rows `Display` writes, which is how the server tells clients
+/// what they are playing on.
+impl Serialize for TileMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TileMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        if text.trim_end().is_empty() {
+            // an open arena has no rows
+            return Ok(TileMap::default());
+        }
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Something lying on the map for tanks to collect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickupKind {
+    Health,
+    Ammo,
+}
+
+impl PickupKind {
+    pub const ALL: [PickupKind; 2] = [PickupKind::Health, PickupKind::Ammo];
+
+    /// How map files write it.
+    pub fn name(self) -> &'static str {
+        match self {
+            PickupKind::Health => "health",
+            PickupKind::Ammo => "ammo",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub position: na::Point2<f32>,
+}
+
+/// Everything a map file describes: its tiles, where each player's tank
+/// starts and where the pickups lie.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Map {
+    pub tiles: TileMap,
+    pub spawns: Vec<(Player, na::Point2<f32>)>,
+    pub pickups: Vec<Pickup>,
+}
+
+impl Map {
+    /// Reads the map at `path` in the resources.
+    pub fn load(ctx: &mut Context, path: &str) -> GameResult<Map> {
+        let mut text = String::new();
+        filesystem::open(ctx, path)?.read_to_string(&mut text)?;
+        text.parse().map_err(|e| match e {
+            GameError::ConfigError(message) => {
+                GameError::ConfigError(format!("{}: {}", path, message))
+            }
+            e => e,
+        })
+    }
+
+    /// Where `player`'s tank starts.
+    pub fn spawn(&self, player: Player) -> Option<na::Point2<f32>> {
+        self.spawns
+            .iter()
+            .find(|(spawn_player, _)| *spawn_player == player)
+            .map(|(_, position)| *position)
+    }
+}
+
+/// A map file is a list of entries, one per line, followed by the tiles:
+///
+/// ```text
+/// // comments and blank lines are ignored
+/// size 24 18
+/// spawn P1 1050 450
+/// pickup health 600 450
+/// tiles
+/// ........................
+/// .....#..#......#..#.....
+/// ```
+///
+/// `size` gives the columns and rows of tiles, every tile is `TILE_SIZE`
+/// pixels square. Each of P1 to P4 needs one `spawn`, the centre of their
+/// tank in pixels; it must lie on the map clear of walls and water. Any number
+/// of `pickup health` or `pickup ammo` lines place pickups, also in pixels.
+/// After `tiles` come exactly `size` rows, written as `TileMap` reads them.
+/// Errors name the line that caused them.
+impl FromStr for Map {
+    type Err = GameError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lines = numbered_lines(text.trim_end());
+        let mut size = None;
+        let mut spawns: Vec<(usize, Player, na::Point2<f32>)> = Vec::new();
+        let mut pickups: Vec<(usize, Pickup)> = Vec::new();
+        let mut tiles_at = None;
+
+        for (index, (number, line)) in lines.iter().enumerate() {
+            let number = *number;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                [comment, ..] if comment.starts_with("//") => {}
+                ["size", args @ ..] => {
+                    let parsed = parse_size(args)
+                        .ok_or_else(|| line_error(number, "expected `size <columns> <rows>`"))?;
+                    size = Some(parsed);
+                }
+                ["spawn", args @ ..] => {
+                    let (player, position) = parse_spawn(args)
+                        .ok_or_else(|| line_error(number, "expected `spawn <P1 to P4> <x> <y>`"))?;
+                    if let Some((earlier, _, _)) = spawns.iter().find(|(_, p, _)| *p == player) {
+                        return Err(line_error(
+                            number,
+                            format!("{:?} already spawns on line {}", player, earlier),
+                        ));
+                    }
+                    spawns.push((number, player, position));
+                }
+                ["pickup", args @ ..] => {
+                    let pickup = parse_pickup(args).ok_or_else(|| {
+                        line_error(number, "expected `pickup <health or ammo> <x> <y>`")
+                    })?;
+                    pickups.push((number, pickup));
+                }
+                ["tiles"] => {
+                    tiles_at = Some(index);
+                    break;
+                }
+                _ => {
+                    return Err(line_error(
+                        number,
+                        format!(
+                            "unknown entry {:?}, expected size, spawn, pickup or tiles",
+                            line.trim()
+                        ),
+                    ))
+                }
+            }
+        }
+
+        let (columns, rows) =
+            size.ok_or_else(|| GameError::ConfigError("the map has no size".to_owned()))?;
+        let tiles_at =
+            tiles_at.ok_or_else(|| GameError::ConfigError("the map has no tiles".to_owned()))?;
+        let (tiles_line, _) = lines[tiles_at];
+        let grid = &lines[tiles_at + 1..];
+        if grid.len() != rows {
+            return Err(line_error(
+                tiles_line,
+                format!(
+                    "{} rows of tiles follow, the size says {}",
+                    grid.len(),
+                    rows
+                ),
+            ));
+        }
+        let tiles = parse_rows(grid, columns)?;
+
+        let bounds = tiles.bounds();
+        for (number, player, position) in &spawns {
+            let hitbox = Obb::new(*position, TANK_DIMENSIONS.w, TANK_DIMENSIONS.h, 0.);
+            let body = hitbox.bounding_rect();
+            if body.left() < bounds.left()
+                || body.top() < bounds.top()
+                || body.right() > bounds.right()
+                || body.bottom() > bounds.bottom()
+            {
+                return Err(line_error(
+                    *number,
+                    format!("{:?}'s tank doesn't fit on the map there", player),
+                ));
+            }
+            if tiles.blocks_tank(&hitbox) {
+                return Err(line_error(
+                    *number,
+                    format!("{:?}'s tank would start in a wall or water", player),
+                ));
+            }
+        }
+        for (number, pickup) in &pickups {
+            if !bounds.contains(pickup.position) {
+                return Err(line_error(*number, "the pickup is off the map"));
+            }
+            if tiles.tile_at(pickup.position).blocks_tanks() {
+                return Err(line_error(*number, "tanks can't reach the pickup"));
+            }
+        }
+        if let Some(player) = Player::ALL
+            .iter()
+            .find(|player| !spawns.iter().any(|(_, p, _)| p == *player))
+        {
+            return Err(GameError::ConfigError(format!(
+                "the map has no spawn for {:?}",
+                player
+            )));
+        }
+
+        Ok(Map {
+            tiles,
+            spawns: spawns
+                .into_iter()
+                .map(|(_, player, position)| (player, position))
+                .collect(),
+            pickups: pickups.into_iter().map(|(_, pickup)| pickup).collect(),
+        })
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "size {} {}", self.tiles.columns, self.tiles.rows)?;
+        for (player, position) in &self.spawns {
+            writeln!(f, "spawn {:?} {} {}", player, position.x, position.y)?;
+        }
+        for pickup in &self.pickups {
+            writeln!(
+                f,
+                "pickup {} {} {}",
+                pickup.kind.name(),
+                pickup.position.x,
+                pickup.position.y
+            )?;
+        }
+        writeln!(f, "tiles")?;
+        write!(f, "{}", self.tiles)
+    }
+}
+
+/// `text`'s lines with their numbers, counting from 1, and without trailing spaces.
+fn numbered_lines(text: &str) -> Vec<(usize, &str)> {
+    text.lines()
+        .map(str::trim_end)
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .collect()
+}
+
+/// One row of `columns` tiles per line. The tiles are collected as the rows
+/// are checked, so a bogus size can't allocate more than the file holds.
+fn parse_rows(lines: &[(usize, &str)], columns: usize) -> GameResult<TileMap> {
+    let mut tiles = Vec::new();
+    for (number, line) in lines {
+        let width = line.chars().count();
+        if width != columns {
+            return Err(line_error(
+                *number,
+                format!("{} tiles wide, expected {}", width, columns),
+            ));
+        }
+        for (column, c) in line.chars().enumerate() {
+            let tile = Tile::from_char(c).ok_or_else(|| {
+                GameError::ConfigError(format!(
+                    "line {}, column {}: unknown tile {:?}",
+                    number,
+                    column + 1,
+                    c
+                ))
+            })?;
+            tiles.push(tile);
+        }
+    }
+    Ok(TileMap {
+        columns,
+        rows: lines.len(),
+        tiles,
+    })
+}
+
+fn parse_size(args: &[&str]) -> Option<(usize, usize)> {
+    match args {
+        [columns, rows] => Some((columns.parse().ok()?, rows.parse().ok()?)).filter(
+            |(columns, rows): &(usize, usize)| {
+                *columns > 0 && *rows > 0 && columns.checked_mul(*rows).is_some()
+            },
+        ),
+        _ => None,
+    }
+}
+
+fn parse_spawn(args: &[&str]) -> Option<(Player, na::Point2<f32>)> {
+    match args {
+        [player, x, y] => {
+            let player = Player::ALL
+                .iter()
+                .find(|candidate| format!("{:?}", candidate) == *player)?;
+            Some((*player, parse_position(x, y)?))
+        }
+        _ => None,
+    }
+}
+
+fn parse_pickup(args: &[&str]) -> Option<Pickup> {
+    match args {
+        [kind, x, y] => Some(Pickup {
+            kind: *PickupKind::ALL
+                .iter()
+                .find(|candidate| candidate.name() == *kind)?,
+            position: parse_position(x, y)?,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_position(x: &str, y: &str) -> Option<na::Point2<f32>> {
+    Some(na::Point2::from([x.parse().ok()?, y.parse().ok()?]))
+}
+
+fn line_error(number: usize, message: impl fmt::Display) -> GameError {
+    GameError::ConfigError(format!("line {}: {}", number, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_block_tanks_and_bullets_differently() {
+        assert!(Tile::Wall.blocks_tanks() && Tile::Wall.blocks_bullets());
+        assert!(Tile::Water.blocks_tanks() && !Tile::Water.blocks_bullets());
+        assert!(!Tile::Cover.blocks_tanks() && Tile::Cover.blocks_bullets());
+        assert!(!Tile::Floor.blocks_tanks() && !Tile::Floor.blocks_bullets());
+    }
+
+    #[test]
+    fn parses_one_row_per_line() {
+        let map: TileMap = "#..\n~%.\n\n".parse().unwrap();
+
+        assert_eq!((map.columns, map.rows), (3, 2));
+        assert_eq!(map.tile(0, 0), Tile::Wall);
+        assert_eq!(map.tile(0, 1), Tile::Water);
+        assert_eq!(map.tile(1, 1), Tile::Cover);
+        assert_eq!(map.bounds(), Rect::new(0., 0., 150., 100.));
+        assert_eq!(map.to_string(), "#..\n~%.\n");
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_line() {
+        let error = |text: &str| match text.parse::<TileMap>() {
+            Err(GameError::ConfigError(message)) => message,
+            other => panic!("expected a config error, got {:?}", other),
+        };
+
+        assert_eq!(error("...\n..\n"), "line 2: 2 tiles wide, expected 3");
+        assert_eq!(error("...\n...\n.x."), "line 3, column 2: unknown tile 'x'");
+        assert_eq!(error("\n"), "the map has no tiles");
+    }
+
+    #[test]
+    fn outside_the_map_is_floor() {
+        let map: TileMap = "##\n##".parse().unwrap();
+
+        assert_eq!(map.tile_at(na::Point2::from([99., 99.])), Tile::Wall);
+        assert_eq!(map.tile_at(na::Point2::from([100., 20.])), Tile::Floor);
+        assert_eq!(map.tile_at(na::Point2::from([-1., 20.])), Tile::Floor);
+        assert!(TileMap::default().tank_obstacles().is_empty());
+    }
+
+    #[test]
+    fn tanks_collide_with_the_tiles_their_body_covers() {
+        let map: TileMap = "...\n.~.\n...".parse().unwrap();
+        // a thin box at 45 degrees poking into the corner of the water tile
+        let diagonal = Obb::new(
+            na::Point2::from([35., 35.]),
+            60.,
+            4.,
+            std::f32::consts::FRAC_PI_4,
+        );
+        let beside = Obb::new(na::Point2::from([125., 25.]), 40., 40., 0.);
+
+        assert!(map.blocks_tank(&diagonal));
+        assert!(!map.blocks_tank(&beside));
+    }
+
+    #[test]
+    fn map_files_place_tanks_and_pickups() {
+        let map: Map = MAP.parse().unwrap();
+
+        assert_eq!((map.tiles.columns, map.tiles.rows), (8, 4));
+        assert_eq!(map.tiles.tile(3, 1), Tile::Wall);
+        assert_eq!(map.spawn(Player::P1), Some(na::Point2::from([325., 100.])));
+        assert_eq!(map.spawn(Player::P4), Some(na::Point2::from([75., 150.])));
+        assert_eq!(
+            map.pickups,
+            vec![Pickup {
+                kind: PickupKind::Ammo,
+                position: na::Point2::from([200., 25.]),
+            }]
+        );
+        assert_eq!(map.to_string().parse::<Map>().unwrap(), map);
+    }
+
+    #[test]
+    fn map_errors_point_at_the_offending_line() {
+        let error = |from: &str, to: &str| match MAP.replacen(from, to, 1).parse::<Map>() {
+            Err(GameError::ConfigError(message)) => message,
+            other => panic!("expected a config error, got {:?}", other),
+        };
+
+        assert_eq!(
+            error("size 8 4", "size 8"),
+            "line 2: expected `size <columns> <rows>`"
+        );
+        assert_eq!(
+            error("spawn P2", "spawn P5"),
+            "line 4: expected `spawn <P1 to P4> <x> <y>`"
+        );
+        assert_eq!(
+            error("spawn P2", "spawn P1"),
+            "line 4: P1 already spawns on line 3"
+        );
+        assert_eq!(
+            error("P3 75 50", "P3 25 50"),
+            "line 5: P3's tank doesn't fit on the map there"
+        );
+        assert_eq!(
+            error("P3 75 50", "P3 175 100"),
+            "line 5: P3's tank would start in a wall or water"
+        );
+        assert_eq!(error("spawn P4 75 150", ""), "the map has no spawn for P4");
+        assert_eq!(
+            error("pickup ammo", "pickup armour"),
+            "line 7: expected `pickup <health or ammo> <x> <y>`"
+        );
+        assert_eq!(
+            error("ammo 200 25", "ammo 500 25"),
+            "line 7: the pickup is off the map"
+        );
+        assert_eq!(
+            error("ammo 200 25", "ammo 175 75"),
+            "line 7: tanks can't reach the pickup"
+        );
+        assert_eq!(
+            error("tiles", "tile"),
+            "line 8: unknown entry \"tile\", expected size, spawn, pickup or tiles"
+        );
+        assert_eq!(
+            error("........\n", ""),
+            "line 8: 3 rows of tiles follow, the size says 4"
+        );
+        assert_eq!(
+            error("...#....", "...#..."),
+            "line 10: 7 tiles wide, expected 8"
+        );
+    }
+
+    #[test]
+    fn oversized_maps_are_refused_before_allocating() {
+        let error = |size: &str| match MAP.replacen("size 8 4", size, 1).parse::<Map>() {
+            Err(GameError::ConfigError(message)) => message,
+            other => panic!("expected a config error, got {:?}", other),
+        };
+
+        assert_eq!(
+            error("size 2400000000 4"),
+            "line 9: 8 tiles wide, expected 2400000000"
+        );
+        assert_eq!(
+            error("size 18446744073709551615 2"),
+            "line 2: expected `size <columns> <rows>`"
+        );
+    }
+
+    #[test]
+    fn open_arenas_fill_one_window() {
+        let mode = crate::state::window_state_mode();
+
+        assert_eq!(
+            TileMap::default().arena(),
+            Rect::new(0., 0., mode.width, mode.height)
+        );
+        assert_eq!(
+            TileMap::new(3, 2).arena(),
+            Rect::new(0., 0., 3. * TILE_SIZE, 2. * TILE_SIZE)
+        );
+    }
+
+    #[test]
+    fn shipped_arena_fills_the_window() {
+        let map: Map = include_str!("../../resources/maps/arena.map")
+            .parse()
+            .unwrap();
+        let mode = crate::state::window_state_mode();
+
+        assert_eq!(
+            map.tiles.bounds(),
+            Rect::new(0., 0., mode.width, mode.height)
+        );
+    }
+
+    #[test]
+    fn shipped_outpost_is_larger_than_the_window() {
+        let map: Map = include_str!("../../resources/maps/outpost.map")
+            .parse()
+            .unwrap();
+        let mode = crate::state::window_state_mode();
+
+        assert!(map.tiles.bounds().w > mode.width && map.tiles.bounds().h > mode.height);
+    }
+
+    const MAP: &str = "// a small map
+size 8 4
+spawn P1 325 100
+spawn P2 75 100
+spawn P3 75 50
+spawn P4 75 150
+pickup ammo 200 25
+tiles
+........
+...#....
+...#....
+........
+";
+}
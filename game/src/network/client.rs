@@ -0,0 +1,889 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use ggez::event::{self, Axis, Button, GamepadId, KeyCode, KeyMods};
+use ggez::graphics::{self, Rect};
+use ggez::nalgebra as na;
+use ggez::Context;
+
+use crate::camera::Camera;
+use crate::collision::Obb;
+use crate::input::{DeviceState, GamepadEvent, Gamepads, InputMap, CONTROLS_PATH};
+use crate::map::TileMap;
+use crate::network::interpolation::Interpolation;
+use crate::network::prediction::Prediction;
+use crate::network::protocol::{
+    decode, encode, BulletSnapshot, ClientMessage, Lobby, PlayerInput, SequencedInput,
+    ServerMessage, Snapshot,
+};
+use crate::network::{MAX_DATAGRAM_SIZE, TICK_RATE};
+use crate::objects::bullet::Bullet;
+use crate::objects::{tank_hitboxes, Tank};
+use crate::state::{Player, BACKGROUND, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+const CONNECT_ATTEMPTS: u32 = 10;
+const CONNECT_RETRY: Duration = Duration::from_millis(200);
+/// Snapshots kept around to decode deltas against.
+const RECEIVED_SNAPSHOTS: usize = 64;
+
+/// Connection to a `Server`, holding the player slot it assigned to us and
+/// the map it plays on.
+pub struct Client {
+    socket: UdpSocket,
+    player: Player,
+    map: TileMap,
+    received: VecDeque<Snapshot>,
+    lobby: Option<Lobby>,
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(server: A) -> io::Result<Client> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(server)?;
+        socket.set_read_timeout(Some(CONNECT_RETRY))?;
+        let join = encode(&ClientMessage::Join)?;
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+
+        for _ in 0..CONNECT_ATTEMPTS {
+            socket.send(&join)?;
+            match socket.recv(&mut buffer) {
+                Ok(len) => match decode(&buffer[..len])? {
+                    ServerMessage::Welcome { player, map } => {
+                        socket.set_nonblocking(true)?;
+                        return Ok(Client {
+                            socket,
+                            player,
+                            map,
+                            received: VecDeque::new(),
+                            lobby: None,
+                        });
+                    }
+                    ServerMessage::Full => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::ConnectionRefused,
+                            "server is full",
+                        ))
+                    }
+                    ServerMessage::MatchInProgress => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::ConnectionRefused,
+                            "match already started",
+                        ))
+                    }
+                    ServerMessage::Snapshot(_)
+                    | ServerMessage::Delta(_)
+                    | ServerMessage::Lobby(_) => {}
+                },
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "server did not answer",
+        ))
+    }
+
+    pub fn player(&self) -> Player {
+        self.player
+    }
+
+    pub fn map(&self) -> &TileMap {
+        &self.map
+    }
+
+    /// Newest lobby state, until the match starts.
+    pub fn lobby(&self) -> Option<&Lobby> {
+        self.lobby.as_ref()
+    }
+
+    pub fn set_ready(&self, ready: bool) -> io::Result<()> {
+        self.socket.send(&encode(&ClientMessage::Ready(ready))?)?;
+        Ok(())
+    }
+
+    pub fn send_inputs(&self, inputs: Vec<SequencedInput>) -> io::Result<()> {
+        self.socket.send(&encode(&ClientMessage::Input(inputs))?)?;
+        Ok(())
+    }
+
+    /// Drains every pending datagram and returns the most recent snapshot, if
+    /// any, acknowledging it so the server can send deltas against it. Lobby
+    /// updates are kept for `lobby`.
+    pub fn latest_snapshot(&mut self) -> io::Result<Option<Snapshot>> {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        let mut latest: Option<Snapshot> = None;
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(len) => {
+                    let snapshot = match decode(&buffer[..len]) {
+                        Ok(ServerMessage::Snapshot(snapshot)) => snapshot,
+                        Ok(ServerMessage::Delta(delta)) => {
+                            match self
+                                .received
+                                .iter()
+                                .find(|base| base.tick == delta.base_tick)
+                                .map(|base| delta.apply_to(base))
+                            {
+                                Some(Ok(snapshot)) => snapshot,
+                                _ => continue,
+                            }
+                        }
+                        Ok(ServerMessage::Lobby(lobby)) => {
+                            self.lobby = Some(lobby);
+                            continue;
+                        }
+                        _ => continue,
+                    };
+                    self.remember(snapshot.clone());
+                    match &latest {
+                        Some(newest) if newest.tick >= snapshot.tick => {}
+                        _ => latest = Some(snapshot),
+                    }
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::ConnectionRefused =>
+                {
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Some(snapshot) = &latest {
+            let ack = ClientMessage::Ack {
+                tick: snapshot.tick,
+            };
+            self.socket.send(&encode(&ack)?)?;
+        }
+        Ok(latest)
+    }
+
+    fn remember(&mut self, snapshot: Snapshot) {
+        self.received.push_back(snapshot);
+        if self.received.len() > RECEIVED_SNAPSHOTS {
+            self.received.pop_front();
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Ok(leave) = encode(&ClientMessage::Leave) {
+            let _ = self.socket.send(&leave);
+        }
+    }
+}
+
+/// Game state of a networked client. It waits in the lobby until every player
+/// is ready; then the local tank is predicted from its own inputs, which are
+/// also sent to the server, and every other tank and bullet is rendered
+/// interpolated between the server's snapshots.
+pub struct ClientState {
+    pub client: Client,
+    pub tank: Tank,
+    pub prediction: Prediction,
+    pub interpolation: Interpolation,
+    pub remote_tanks: Vec<Tank>,
+    pub bullets: Vec<Bullet>,
+    /// The server's tiles, which stop the predicted tank as they stop it there.
+    pub map: TileMap,
+    pub coordinate: Rect,
+    /// Scales the server's arena to fit the window.
+    pub camera: Camera,
+    pub input_map: InputMap,
+    pub gamepads: Gamepads,
+    pub ready: bool,
+    in_match: bool,
+    last_tick: u64,
+}
+
+impl ClientState {
+    pub fn new(ctx: &mut Context, client: Client) -> ggez::GameResult<ClientState> {
+        let map = client.map().clone();
+        let sc = map.arena();
+        let tank = Tank::new(
+            ctx,
+            client.player(),
+            na::Point2::from([sc.w / 2., sc.h / 2.]),
+        )?;
+
+        Ok(ClientState {
+            client,
+            tank,
+            prediction: Prediction::new(),
+            interpolation: Interpolation::new(),
+            remote_tanks: Vec::new(),
+            bullets: Vec::new(),
+            map,
+            coordinate: sc,
+            camera: Camera::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT),
+            input_map: InputMap::load(ctx, CONTROLS_PATH)?,
+            gamepads: Gamepads::default(),
+            ready: false,
+            in_match: false,
+            last_tick: 0,
+        })
+    }
+
+    pub fn in_match(&self) -> bool {
+        self.in_match
+    }
+
+    /// Predicts `input` locally, sends it to the server and applies the newest
+    /// snapshot. In the lobby only the ready state is sent.
+    pub fn step(&mut self, input: PlayerInput) -> io::Result<()> {
+        if self.in_match && !self.tank.is_destroyed() {
+            let others = self.obstacles();
+            self.prediction
+                .predict(&mut self.tank, input, self.coordinate, &others);
+            self.client.send_inputs(self.prediction.unacknowledged())?;
+        } else if !self.in_match {
+            self.client.set_ready(self.ready)?;
+        }
+
+        if let Some(snapshot) = self.client.latest_snapshot()? {
+            self.apply_snapshot(&snapshot);
+        }
+        Ok(())
+    }
+
+    pub fn apply_snapshot(&mut self, snapshot: &Snapshot) {
+        if snapshot.tick < self.last_tick {
+            return;
+        }
+        self.last_tick = snapshot.tick;
+        self.in_match = true;
+
+        match snapshot
+            .tanks
+            .iter()
+            .find(|tank| tank.player == self.tank.player)
+        {
+            Some(local) => {
+                let others = self.obstacles();
+                self.prediction
+                    .reconcile(&mut self.tank, local, self.coordinate, &others);
+                self.tank.health = local.health;
+            }
+            // the server drops destroyed tanks from its snapshots
+            None => self.tank.health = 0,
+        }
+
+        self.interpolation.push(snapshot.clone());
+        self.update_remotes();
+    }
+
+    /// What the local tank can't drive through: the other tanks and the
+    /// map's walls and water.
+    fn obstacles(&self) -> Vec<Obb> {
+        let mut obstacles = tank_hitboxes(self.tank.player, self.remote_tanks.iter());
+        obstacles.extend(self.map.tank_obstacles());
+        obstacles
+    }
+
+    /// Moves remote tanks and bullets `delta` seconds forward along the buffered snapshots.
+    pub fn interpolate(&mut self, delta: f32) {
+        self.interpolation.advance(delta);
+        self.update_remotes();
+    }
+
+    fn update_remotes(&mut self) {
+        let snapshot = match self.interpolation.sample() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        for tank_snapshot in &snapshot.tanks {
+            if tank_snapshot.player == self.tank.player {
+                continue;
+            }
+
+            match self
+                .remote_tanks
+                .iter_mut()
+                .find(|tank| tank.player == tank_snapshot.player)
+            {
+                Some(tank) => tank.apply_snapshot(tank_snapshot),
+                None => {
+                    let mut tank = Tank {
+                        texture: self.tank.texture.clone(),
+                        turret_texture: self.tank.turret_texture.clone(),
+                        ..Tank::headless(tank_snapshot.player, self.tank.position)
+                    };
+                    tank.apply_snapshot(tank_snapshot);
+                    self.remote_tanks.push(tank);
+                }
+            }
+        }
+
+        self.remote_tanks
+            .retain(|tank| snapshot.tanks.iter().any(|t| t.player == tank.player));
+
+        self.bullets = snapshot
+            .bullets
+            .iter()
+            .map(BulletSnapshot::to_bullet)
+            .collect();
+    }
+
+    fn lobby_lines(&self) -> Vec<String> {
+        let mut lines = vec![String::from("Press Enter when ready")];
+        if let Some(lobby) = self.client.lobby() {
+            lines.extend(lobby.players.iter().map(|slot| {
+                format!(
+                    "{:?}{} - {}",
+                    slot.player,
+                    if slot.player == self.tank.player {
+                        " (you)"
+                    } else {
+                        ""
+                    },
+                    if slot.ready { "ready" } else { "waiting" }
+                )
+            }));
+        }
+        lines
+    }
+}
+
+impl event::EventHandler for ClientState {
+    fn update(&mut self, ctx: &mut Context) -> ggez::GameResult {
+        let mut devices =
+            DeviceState::from_context(ctx).with_gamepad(&self.gamepads, self.input_map.gamepad);
+        devices.mouse_position = self.camera.screen_to_world(devices.mouse_position);
+        let input = self.input_map.input(&devices, Some(&self.tank));
+
+        // one input per server tick, however fast frames are drawn
+        while ggez::timer::check_update_time(ctx, TICK_RATE) {
+            self.step(input)?;
+        }
+        self.interpolate(ggez::timer::delta(ctx).as_secs_f32());
+        Ok(())
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _: KeyMods, _: bool) {
+        match keycode {
+            KeyCode::Escape => event::quit(ctx),
+            KeyCode::Return if !self.in_match => self.ready = !self.ready,
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> ggez::GameResult {
+        if self.in_match {
+            self.camera.follow(self.tank.position, self.coordinate);
+        }
+        self.camera.begin_frame(ctx, BACKGROUND.into())?;
+        if !self.in_match {
+            for (i, line) in self.lobby_lines().into_iter().enumerate() {
+                let param = graphics::DrawParam::new()
+                    .dest(na::Point2::from([40., 40. + 30. * i as f32]))
+                    .color(graphics::BLACK);
+                graphics::draw(ctx, &graphics::Text::new(line), param)?;
+            }
+            graphics::present(ctx)?;
+            return Ok(());
+        }
+        self.map.draw(ctx)?;
+        if !self.tank.is_destroyed() {
+            self.tank.draw(ctx)?;
+        }
+        for tank in &mut self.remote_tanks {
+            tank.draw(ctx)?;
+        }
+        for bullet in &mut self.bullets {
+            bullet.draw(ctx)?;
+        }
+        graphics::present(ctx)?;
+        Ok(())
+    }
+
+    fn resize_event(&mut self, _: &mut Context, width: f32, height: f32) {
+        self.camera.resize(width, height);
+    }
+
+    fn gamepad_button_down_event(&mut self, _: &mut Context, btn: Button, id: GamepadId) {
+        self.gamepads.event(id, GamepadEvent::ButtonDown(btn));
+    }
+
+    fn gamepad_button_up_event(&mut self, _: &mut Context, btn: Button, id: GamepadId) {
+        self.gamepads.event(id, GamepadEvent::ButtonUp(btn));
+    }
+
+    fn gamepad_axis_event(&mut self, _: &mut Context, axis: Axis, value: f32, id: GamepadId) {
+        self.gamepads.event(id, GamepadEvent::Axis(axis, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+    use crate::map::Map;
+    use crate::network::local::LocalServer;
+    use crate::network::loopback::LossyLoopback;
+    use crate::network::protocol::TankSnapshot;
+    use crate::network::server::Server;
+    use crate::objects::TANK_DIMENSIONS;
+
+    #[test]
+    fn clients_connect_over_loopback() {
+        let server = LocalServer::spawn("127.0.0.1:0").unwrap();
+
+        let first = Client::connect(server.local_addr()).unwrap();
+        let second = Client::connect(server.local_addr()).unwrap();
+
+        assert_eq!(first.player(), Player::P1);
+        assert_eq!(second.player(), Player::P2);
+    }
+
+    #[test]
+    fn connecting_to_a_full_server_is_refused() {
+        let server = LocalServer::spawn("127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+
+        let clients: Vec<Client> = (0..4).map(|_| Client::connect(addr).unwrap()).collect();
+        let refused = Client::connect(addr);
+
+        assert_eq!(clients.len(), 4);
+        assert_eq!(
+            refused.err().map(|e| e.kind()),
+            Some(io::ErrorKind::ConnectionRefused)
+        );
+    }
+
+    #[test]
+    fn match_starts_once_every_client_is_ready() {
+        let server = LocalServer::spawn("127.0.0.1:0").unwrap();
+        let mut first = ClientState::headless(Client::connect(server.local_addr()).unwrap());
+        let mut second = ClientState::headless(Client::connect(server.local_addr()).unwrap());
+        first.ready = true;
+
+        for _ in 0..20 {
+            first.step(PlayerInput::default()).unwrap();
+            second.step(PlayerInput::default()).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(!first.in_match());
+        assert_eq!(
+            first.lobby_lines(),
+            vec!["Press Enter when ready", "P1 (you) - ready", "P2 - waiting"]
+        );
+
+        second.ready = true;
+        for _ in 0..1_000 {
+            if first.in_match() && second.in_match() {
+                break;
+            }
+            first.step(PlayerInput::default()).unwrap();
+            second.step(PlayerInput::default()).unwrap();
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        assert_eq!(first.remote_tanks.len(), 1);
+        assert_eq!(first.remote_tanks[0].player, Player::P2);
+        assert_eq!(second.tank.position, na::Point2::from([150., 450.]));
+    }
+
+    #[test]
+    fn joining_a_running_match_is_refused() {
+        let server = LocalServer::spawn("127.0.0.1:0").unwrap();
+        let _playing = connected_state(server.local_addr());
+
+        let late = Client::connect(server.local_addr());
+
+        assert_eq!(
+            late.err().map(|e| e.kind()),
+            Some(io::ErrorKind::ConnectionRefused)
+        );
+    }
+
+    #[test]
+    fn snapshot_spawns_moves_and_removes_remote_tanks() {
+        let mut state = client_state();
+
+        state.apply_snapshot(&Snapshot {
+            tick: 1,
+            tanks: vec![
+                tank_snapshot(Player::P1, 10.),
+                tank_snapshot(Player::P2, 20.),
+            ],
+            bullets: vec![],
+        });
+
+        assert_eq!(state.tank.position, na::Point2::from([10., 10.]));
+        assert_eq!(state.remote_tanks.len(), 1);
+        assert_eq!(state.remote_tanks[0].player, Player::P2);
+        assert_eq!(state.remote_tanks[0].position, na::Point2::from([20., 20.]));
+
+        state.apply_snapshot(&Snapshot {
+            tick: 2,
+            tanks: vec![
+                tank_snapshot(Player::P1, 10.),
+                tank_snapshot(Player::P3, 30.),
+            ],
+            bullets: vec![],
+        });
+
+        state.interpolate(1.);
+
+        assert_eq!(state.remote_tanks.len(), 1);
+        assert_eq!(state.remote_tanks[0].player, Player::P3);
+    }
+
+    #[test]
+    fn remote_tanks_are_interpolated_between_snapshots() {
+        let mut state = client_state();
+        state.apply_snapshot(&Snapshot {
+            tick: 10,
+            tanks: vec![tank_snapshot(Player::P2, 100.)],
+            bullets: vec![],
+        });
+        state.apply_snapshot(&Snapshot {
+            tick: 20,
+            tanks: vec![tank_snapshot(Player::P2, 200.)],
+            bullets: vec![],
+        });
+
+        state.interpolate(11. / crate::network::TICK_RATE as f32);
+
+        assert_eq!(
+            state.remote_tanks[0].position,
+            na::Point2::from([150., 150.])
+        );
+    }
+
+    #[test]
+    fn deltas_are_decoded_against_received_snapshots() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut client = client_of(&server);
+        let base = Snapshot {
+            tick: 1,
+            tanks: vec![tank_snapshot(Player::P1, 10.)],
+            bullets: vec![],
+        };
+        let next = Snapshot {
+            tick: 2,
+            tanks: vec![tank_snapshot(Player::P1, 11.)],
+            bullets: vec![],
+        };
+
+        reply(&server, &client, &ServerMessage::Snapshot(base.clone()));
+        assert_eq!(client.latest_snapshot().unwrap(), Some(base.clone()));
+        reply(
+            &server,
+            &client,
+            &ServerMessage::Delta(next.delta_from(&base)),
+        );
+        assert_eq!(client.latest_snapshot().unwrap(), Some(next));
+
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        let acks: Vec<ClientMessage> = (0..2)
+            .map(|_| {
+                let len = server.recv(&mut buffer).unwrap();
+                decode(&buffer[..len]).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            acks,
+            vec![
+                ClientMessage::Ack { tick: 1 },
+                ClientMessage::Ack { tick: 2 }
+            ]
+        );
+    }
+
+    #[test]
+    fn deltas_against_unknown_snapshots_are_dropped() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut client = client_of(&server);
+        let base = Snapshot {
+            tick: 1,
+            tanks: vec![tank_snapshot(Player::P1, 10.)],
+            bullets: vec![],
+        };
+        let next = Snapshot {
+            tick: 2,
+            ..base.clone()
+        };
+
+        reply(
+            &server,
+            &client,
+            &ServerMessage::Delta(next.delta_from(&base)),
+        );
+
+        assert_eq!(client.latest_snapshot().unwrap(), None);
+    }
+
+    #[test]
+    fn local_tank_moves_before_the_server_answers() {
+        let (addr, running, handle) = run_server_behind(Duration::from_millis(100), 0.);
+        let mut state = connected_state(addr);
+        let spawn = state.tank.position;
+
+        state.step(forward()).unwrap();
+
+        assert_eq!(state.tank.position.x, spawn.x - 1.);
+        assert_eq!(state.prediction.pending(), 1);
+        running.store(false, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn prediction_converges_with_latency_and_packet_loss() {
+        let (addr, running, handle) = run_server_behind(Duration::from_millis(40), 0.2);
+        let mut state = connected_state(addr);
+        let mut frames = 0;
+
+        for _ in 0..60 {
+            state.step(forward()).unwrap();
+            frames += 1;
+            thread::sleep(Duration::from_millis(3));
+        }
+        while state.prediction.pending() > 0 && frames < 2_000 {
+            state.step_idle();
+            frames += 1;
+            thread::sleep(Duration::from_millis(3));
+        }
+
+        assert_eq!(state.prediction.pending(), 0);
+        assert_eq!(state.tank.position, na::Point2::from([540., 450.]));
+        running.store(false, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn local_tank_is_predicted_against_the_servers_map() {
+        let map: Map = "size 12 6
+spawn P1 300 150
+spawn P2 500 75
+spawn P3 500 150
+spawn P4 500 225
+tiles
+............
+...#........
+...#........
+...#........
+...#........
+............"
+            .parse()
+            .unwrap();
+        let server = LocalServer::with_map("127.0.0.1:0", map.clone()).unwrap();
+        let mut state = connected_state(server.local_addr());
+        assert_eq!(state.map, map.tiles);
+        assert_eq!(state.coordinate, map.tiles.bounds());
+        let corrections = state.prediction.corrections();
+
+        // paced like the server's ticks, so none of the inputs are dropped
+        for _ in 0..40 {
+            state.step(forward()).unwrap();
+            thread::sleep(Duration::from_secs(1) / TICK_RATE);
+        }
+        while state.prediction.pending() > 0 {
+            state.step_idle();
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        // the wall's right edge is at 200, the tank stops with its back against it
+        assert_eq!(state.tank.position.x, 200. + TANK_DIMENSIONS.w / 2.);
+        assert_eq!(state.prediction.corrections(), corrections);
+    }
+
+    #[test]
+    fn snapshot_corrects_mispredicted_local_tank() {
+        let mut state = client_state();
+        let coordinate = state.coordinate;
+        state
+            .prediction
+            .predict(&mut state.tank, forward(), coordinate, &[]);
+
+        state.apply_snapshot(&Snapshot {
+            tick: 1,
+            tanks: vec![TankSnapshot {
+                last_input: 1,
+                ..tank_snapshot(Player::P1, 50.)
+            }],
+            bullets: vec![],
+        });
+
+        assert_eq!(state.tank.position, na::Point2::from([50., 50.]));
+        assert_eq!(state.prediction.corrections(), 1);
+    }
+
+    #[test]
+    fn local_tank_missing_from_snapshot_was_destroyed() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut state = ClientState::headless(client_of(&server));
+        state.apply_snapshot(&Snapshot {
+            tick: 1,
+            tanks: vec![tank_snapshot(Player::P2, 20.)],
+            bullets: vec![],
+        });
+
+        state.step(forward()).unwrap();
+
+        assert!(state.tank.is_destroyed());
+        assert_eq!(state.prediction.pending(), 0);
+    }
+
+    #[test]
+    fn stale_snapshots_are_ignored() {
+        let mut state = client_state();
+
+        state.apply_snapshot(&Snapshot {
+            tick: 5,
+            tanks: vec![tank_snapshot(Player::P1, 10.)],
+            bullets: vec![],
+        });
+        state.apply_snapshot(&Snapshot {
+            tick: 4,
+            tanks: vec![tank_snapshot(Player::P1, 20.)],
+            bullets: vec![],
+        });
+
+        assert_eq!(state.tank.position, na::Point2::from([10., 10.]));
+    }
+
+    impl ClientState {
+        fn headless(client: Client) -> ClientState {
+            let map = client.map().clone();
+            ClientState {
+                tank: Tank::headless(client.player(), na::Point2::from([0., 0.])),
+                client,
+                prediction: Prediction::new(),
+                interpolation: Interpolation::new(),
+                remote_tanks: Vec::new(),
+                bullets: Vec::new(),
+                coordinate: map.arena(),
+                map,
+                camera: Camera::new(1200., 900.),
+                input_map: InputMap::default(),
+                gamepads: Gamepads::default(),
+                ready: false,
+                in_match: false,
+                last_tick: 0,
+            }
+        }
+
+        /// Keeps resending unacknowledged inputs and applying snapshots without new input.
+        fn step_idle(&mut self) {
+            let unacknowledged = self.prediction.unacknowledged();
+            if !unacknowledged.is_empty() {
+                self.client.send_inputs(unacknowledged).unwrap();
+            }
+            if let Some(snapshot) = self.client.latest_snapshot().unwrap() {
+                self.apply_snapshot(&snapshot);
+            }
+        }
+    }
+
+    fn run_server_behind(
+        latency: Duration,
+        packet_loss: f32,
+    ) -> (
+        std::net::SocketAddr,
+        Arc<AtomicBool>,
+        thread::JoinHandle<()>,
+    ) {
+        let mut server = Server::bind("127.0.0.1:0").unwrap();
+        let mut link =
+            LossyLoopback::new(server.local_addr().unwrap(), latency, packet_loss, 42).unwrap();
+        let addr = link.local_addr().unwrap();
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        let handle = thread::spawn(move || {
+            while flag.load(Ordering::SeqCst) {
+                link.pump().unwrap();
+                server.tick().unwrap();
+                link.pump().unwrap();
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+        (addr, running, handle)
+    }
+
+    /// A client alone in a running match.
+    fn connected_state(addr: std::net::SocketAddr) -> ClientState {
+        let client = Client::connect(addr).unwrap();
+        let mut state = ClientState {
+            ready: true,
+            ..ClientState::headless(client)
+        };
+        wait_for_match(&mut state);
+        state
+    }
+
+    fn wait_for_match(state: &mut ClientState) {
+        for _ in 0..1_000 {
+            if state.in_match() {
+                return;
+            }
+            state.step(PlayerInput::default()).unwrap();
+            thread::sleep(Duration::from_millis(2));
+        }
+        panic!("match didn't start");
+    }
+
+    fn client_state() -> ClientState {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        ClientState::headless(Client {
+            socket,
+            player: Player::P1,
+            map: TileMap::default(),
+            received: VecDeque::new(),
+            lobby: None,
+        })
+    }
+
+    fn client_of(server: &UdpSocket) -> Client {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.local_addr().unwrap()).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        Client {
+            socket,
+            player: Player::P1,
+            map: TileMap::default(),
+            received: VecDeque::new(),
+            lobby: None,
+        }
+    }
+
+    fn reply(server: &UdpSocket, client: &Client, message: &ServerMessage) {
+        let addr = client.socket.local_addr().unwrap();
+        server.send_to(&encode(message).unwrap(), addr).unwrap();
+    }
+
+    fn forward() -> PlayerInput {
+        PlayerInput {
+            forward: true,
+            aim: [0., 450.],
+            ..PlayerInput::default()
+        }
+    }
+
+    fn tank_snapshot(player: Player, position: f32) -> TankSnapshot {
+        TankSnapshot {
+            player,
+            position: [position, position],
+            tank_rotation: 0.,
+            turret_rotation: 0.,
+            health: 100,
+            last_input: 0,
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::protocol::{BulletSnapshot, Snapshot, TankSnapshot};
+use crate::state::Player;
+
+/// A `Snapshot` encoded as the changes since an older snapshot the client
+/// acknowledged. Tanks that didn't change are left out entirely and bullets
+/// are only sent when the set of bullets changed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotDelta {
+    pub base_tick: u64,
+    pub tick: u64,
+    pub tanks: Vec<TankDelta>,
+    pub removed_tanks: Vec<Player>,
+    pub bullets: Option<Vec<BulletSnapshot>>,
+}
+
+/// Fields of a `TankSnapshot` that differ from the base, `None` when unchanged.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TankDelta {
+    pub player: Player,
+    pub position: Option<[f32; 2]>,
+    pub tank_rotation: Option<f32>,
+    pub turret_rotation: Option<f32>,
+    pub health: Option<u32>,
+    pub last_input: Option<u32>,
+}
+
+impl Snapshot {
+    pub fn delta_from(&self, base: &Snapshot) -> SnapshotDelta {
+        SnapshotDelta {
+            base_tick: base.tick,
+            tick: self.tick,
+            tanks: self
+                .tanks
+                .iter()
+                .filter_map(
+                    |tank| match base.tanks.iter().find(|old| old.player == tank.player) {
+                        Some(old) => TankDelta::between(old, tank),
+                        None => Some(TankDelta::full(tank)),
+                    },
+                )
+                .collect(),
+            removed_tanks: base
+                .tanks
+                .iter()
+                .filter(|old| self.tanks.iter().all(|tank| tank.player != old.player))
+                .map(|old| old.player)
+                .collect(),
+            bullets: if self.bullets == base.bullets {
+                None
+            } else {
+                Some(self.bullets.clone())
+            },
+        }
+    }
+}
+
+impl SnapshotDelta {
+    /// Rebuilds the full snapshot, `base` must be the snapshot at `base_tick`.
+    pub fn apply_to(&self, base: &Snapshot) -> io::Result<Snapshot> {
+        if base.tick != self.base_tick {
+            return Err(invalid_delta("delta was computed against another snapshot"));
+        }
+
+        let mut tanks: Vec<TankSnapshot> = base
+            .tanks
+            .iter()
+            .filter(|tank| !self.removed_tanks.contains(&tank.player))
+            .copied()
+            .collect();
+        for delta in &self.tanks {
+            match tanks.iter_mut().find(|tank| tank.player == delta.player) {
+                Some(tank) => delta.apply(tank),
+                None => tanks.push(
+                    delta
+                        .into_tank()
+                        .ok_or_else(|| invalid_delta("delta is missing fields of a new tank"))?,
+                ),
+            }
+        }
+
+        Ok(Snapshot {
+            tick: self.tick,
+            tanks,
+            bullets: self.bullets.clone().unwrap_or_else(|| base.bullets.clone()),
+        })
+    }
+}
+
+impl TankDelta {
+    fn full(tank: &TankSnapshot) -> Self {
+        TankDelta {
+            player: tank.player,
+            position: Some(tank.position),
+            tank_rotation: Some(tank.tank_rotation),
+            turret_rotation: Some(tank.turret_rotation),
+            health: Some(tank.health),
+            last_input: Some(tank.last_input),
+        }
+    }
+
+    /// `None` when nothing changed.
+    fn between(old: &TankSnapshot, new: &TankSnapshot) -> Option<Self> {
+        let delta = TankDelta {
+            player: new.player,
+            position: changed(old.position, new.position),
+            tank_rotation: changed(old.tank_rotation, new.tank_rotation),
+            turret_rotation: changed(old.turret_rotation, new.turret_rotation),
+            health: changed(old.health, new.health),
+            last_input: changed(old.last_input, new.last_input),
+        };
+
+        if delta.position.is_none()
+            && delta.tank_rotation.is_none()
+            && delta.turret_rotation.is_none()
+            && delta.health.is_none()
+            && delta.last_input.is_none()
+        {
+            None
+        } else {
+            Some(delta)
+        }
+    }
+
+    fn apply(&self, tank: &mut TankSnapshot) {
+        tank.position = self.position.unwrap_or(tank.position);
+        tank.tank_rotation = self.tank_rotation.unwrap_or(tank.tank_rotation);
+        tank.turret_rotation = self.turret_rotation.unwrap_or(tank.turret_rotation);
+        tank.health = self.health.unwrap_or(tank.health);
+        tank.last_input = self.last_input.unwrap_or(tank.last_input);
+    }
+
+    fn into_tank(self) -> Option<TankSnapshot> {
+        Some(TankSnapshot {
+            player: self.player,
+            position: self.position?,
+            tank_rotation: self.tank_rotation?,
+            turret_rotation: self.turret_rotation?,
+            health: self.health?,
+            last_input: self.last_input?,
+        })
+    }
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<T> {
+    if old == new {
+        None
+    } else {
+        Some(new)
+    }
+}
+
+fn invalid_delta(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::protocol::{decode, encode, ServerMessage};
+
+    #[test]
+    fn delta_round_trip() {
+        let base = snapshot(1, vec![tank(Player::P1, 10.), tank(Player::P2, 20.)]);
+        let next = Snapshot {
+            bullets: vec![bullet(Player::P1)],
+            ..snapshot(2, vec![tank(Player::P1, 11.), tank(Player::P3, 30.)])
+        };
+
+        let delta = next.delta_from(&base);
+
+        assert_eq!(delta.apply_to(&base).unwrap(), next);
+        assert_eq!(delta.removed_tanks, vec![Player::P2]);
+    }
+
+    #[test]
+    fn unchanged_state_is_left_out() {
+        let base = Snapshot {
+            bullets: vec![bullet(Player::P2)],
+            ..snapshot(1, vec![tank(Player::P1, 10.), tank(Player::P2, 20.)])
+        };
+        let next = Snapshot {
+            bullets: vec![bullet(Player::P2)],
+            ..snapshot(2, vec![tank(Player::P1, 10.), tank(Player::P2, 21.)])
+        };
+
+        let delta = next.delta_from(&base);
+
+        assert_eq!(
+            delta.tanks,
+            vec![TankDelta {
+                player: Player::P2,
+                position: Some([21., 21.]),
+                tank_rotation: None,
+                turret_rotation: None,
+                health: None,
+                last_input: None,
+            }]
+        );
+        assert_eq!(delta.bullets, None);
+    }
+
+    #[test]
+    fn damage_is_sent_on_its_own() {
+        let base = snapshot(1, vec![tank(Player::P1, 10.)]);
+        let next = snapshot(
+            2,
+            vec![TankSnapshot {
+                health: 75,
+                ..tank(Player::P1, 10.)
+            }],
+        );
+
+        let delta = next.delta_from(&base);
+
+        assert_eq!(
+            delta.tanks,
+            vec![TankDelta {
+                player: Player::P1,
+                position: None,
+                tank_rotation: None,
+                turret_rotation: None,
+                health: Some(75),
+                last_input: None,
+            }]
+        );
+        assert_eq!(delta.apply_to(&base).unwrap(), next);
+    }
+
+    #[test]
+    fn delta_is_smaller_than_the_full_snapshot() {
+        let tanks: Vec<TankSnapshot> = Player::ALL
+            .iter()
+            .map(|player| tank(*player, 10.))
+            .collect();
+        let base = snapshot(1, tanks.clone());
+        let next = snapshot(2, tanks);
+
+        let full = encode(&ServerMessage::Snapshot(next.clone())).unwrap();
+        let delta = encode(&ServerMessage::Delta(next.delta_from(&base))).unwrap();
+
+        assert!(
+            delta.len() * 4 < full.len(),
+            "{} vs {}",
+            delta.len(),
+            full.len()
+        );
+        assert_eq!(
+            decode::<ServerMessage>(&delta).unwrap(),
+            ServerMessage::Delta(next.delta_from(&base))
+        );
+    }
+
+    #[test]
+    fn wrong_base_is_invalid_data() {
+        let base = snapshot(1, vec![tank(Player::P1, 10.)]);
+        let next = snapshot(3, vec![tank(Player::P1, 11.)]);
+        let delta = next.delta_from(&base);
+
+        let err = delta.apply_to(&snapshot(2, vec![])).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn partial_new_tank_is_invalid_data() {
+        let base = snapshot(1, vec![]);
+        let delta = SnapshotDelta {
+            base_tick: 1,
+            tick: 2,
+            tanks: vec![TankDelta {
+                player: Player::P4,
+                position: Some([1., 1.]),
+                tank_rotation: None,
+                turret_rotation: None,
+                health: None,
+                last_input: None,
+            }],
+            removed_tanks: vec![],
+            bullets: None,
+        };
+
+        assert_eq!(
+            delta.apply_to(&base).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    fn snapshot(tick: u64, tanks: Vec<TankSnapshot>) -> Snapshot {
+        Snapshot {
+            tick,
+            tanks,
+            bullets: vec![],
+        }
+    }
+
+    fn tank(player: Player, position: f32) -> TankSnapshot {
+        TankSnapshot {
+            player,
+            position: [position, position],
+            tank_rotation: 0.5,
+            turret_rotation: -0.5,
+            health: 100,
+            last_input: 3,
+        }
+    }
+
+    fn bullet(owner: Player) -> BulletSnapshot {
+        BulletSnapshot {
+            owner,
+            position: [5., 5.],
+            direction: [1., 0.],
+            rotation: 0.,
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::network::protocol::{BulletSnapshot, Snapshot, TankSnapshot};
+use crate::network::TICK_RATE;
+
+/// How far behind the newest snapshot remote objects are rendered, in seconds.
+pub const INTERPOLATION_DELAY: f32 = 0.1;
+/// How long remote objects keep moving past the newest snapshot when packets stop.
+pub const MAX_EXTRAPOLATION: f32 = 0.25;
+pub const MAX_BUFFERED_SNAPSHOTS: usize = 32;
+
+/// Buffers snapshots from the server and samples remote tanks and bullets
+/// `INTERPOLATION_DELAY` in the past, blending the two snapshots around that
+/// time. When no newer snapshot has arrived, the last two are extrapolated for
+/// at most `MAX_EXTRAPOLATION` before everything freezes in place.
+#[derive(Default)]
+pub struct Interpolation {
+    snapshots: VecDeque<Snapshot>,
+    /// Render time, in server ticks.
+    render_tick: Option<f32>,
+}
+
+impl Interpolation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers `snapshot`, ignoring it if a snapshot with the same or a later tick was seen.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if let Some(newest) = self.snapshots.back() {
+            if newest.tick >= snapshot.tick {
+                return;
+            }
+        }
+        if self.render_tick.is_none() {
+            self.render_tick = Some(snapshot.tick as f32 - delay_ticks());
+        }
+        self.snapshots.push_back(snapshot);
+        if self.snapshots.len() > MAX_BUFFERED_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Moves the render time forward by `delta` seconds.
+    pub fn advance(&mut self, delta: f32) {
+        let (render_tick, newest) = match (self.render_tick, self.snapshots.back()) {
+            (Some(render_tick), Some(newest)) => (render_tick, newest.tick as f32),
+            _ => return,
+        };
+
+        let target = newest - delay_ticks();
+        let mut render_tick = render_tick + delta * TICK_RATE as f32;
+        // fell too far behind (e.g. the window was dragged), catch up instead of replaying
+        if render_tick < target - delay_ticks() {
+            render_tick = target;
+        }
+        self.render_tick = Some(render_tick.min(newest + extrapolation_ticks()));
+
+        while self.snapshots.len() > 2 && (self.snapshots[1].tick as f32) <= render_tick {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Remote tanks and bullets as they should be drawn at the current render time.
+    pub fn sample(&self) -> Option<Snapshot> {
+        let render_tick = self.render_tick?;
+        let newest = self.snapshots.back()?;
+        if self.snapshots.len() == 1 || render_tick <= self.snapshots[0].tick as f32 {
+            return self.snapshots.front().cloned();
+        }
+
+        let to = self
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.tick as f32 >= render_tick)
+            .unwrap_or(self.snapshots.len() - 1)
+            .max(1);
+        let (from, to) = (&self.snapshots[to - 1], &self.snapshots[to]);
+        let render_tick = render_tick.min(newest.tick as f32 + extrapolation_ticks());
+        let alpha = (render_tick - from.tick as f32) / (to.tick - from.tick) as f32;
+
+        Some(Snapshot {
+            tick: render_tick.max(0.) as u64,
+            ..lerp_snapshot(from, to, alpha)
+        })
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.snapshots.len()
+    }
+}
+
+fn delay_ticks() -> f32 {
+    INTERPOLATION_DELAY * TICK_RATE as f32
+}
+
+fn extrapolation_ticks() -> f32 {
+    MAX_EXTRAPOLATION * TICK_RATE as f32
+}
+
+/// Blends every tank and bullet of `to` with its state in `from`; `alpha` 0 is
+/// `from` and 1 is `to`. Objects missing from `from` are taken as they are.
+pub fn lerp_snapshot(from: &Snapshot, to: &Snapshot, alpha: f32) -> Snapshot {
+    Snapshot {
+        tick: to.tick,
+        tanks: to
+            .tanks
+            .iter()
+            .map(|tank| {
+                match from
+                    .tanks
+                    .iter()
+                    .find(|previous| previous.player == tank.player)
+                {
+                    Some(previous) => lerp_tank(previous, tank, alpha),
+                    None => *tank,
+                }
+            })
+            .collect(),
+        bullets: to
+            .bullets
+            .iter()
+            .map(|bullet| match previous_bullet(&from.bullets, bullet) {
+                Some(previous) => lerp_bullet(previous, bullet, alpha),
+                None => *bullet,
+            })
+            .collect(),
+    }
+}
+
+/// Bullets have no id, so the earlier position of `bullet` is the closest one
+/// fired by the same tank in the same direction.
+fn previous_bullet<'a>(
+    bullets: &'a [BulletSnapshot],
+    bullet: &BulletSnapshot,
+) -> Option<&'a BulletSnapshot> {
+    let distance = |other: &BulletSnapshot| {
+        (other.position[0] - bullet.position[0]).powi(2)
+            + (other.position[1] - bullet.position[1]).powi(2)
+    };
+
+    bullets
+        .iter()
+        .filter(|previous| previous.owner == bullet.owner && previous.direction == bullet.direction)
+        .min_by(|a, b| {
+            distance(a)
+                .partial_cmp(&distance(b))
+                .unwrap_or(Ordering::Equal)
+        })
+}
+
+fn lerp_tank(from: &TankSnapshot, to: &TankSnapshot, alpha: f32) -> TankSnapshot {
+    TankSnapshot {
+        position: lerp_point(from.position, to.position, alpha),
+        tank_rotation: lerp_angle(from.tank_rotation, to.tank_rotation, alpha),
+        turret_rotation: lerp_angle(from.turret_rotation, to.turret_rotation, alpha),
+        ..*to
+    }
+}
+
+fn lerp_bullet(from: &BulletSnapshot, to: &BulletSnapshot, alpha: f32) -> BulletSnapshot {
+    BulletSnapshot {
+        position: lerp_point(from.position, to.position, alpha),
+        rotation: lerp_angle(from.rotation, to.rotation, alpha),
+        ..*to
+    }
+}
+
+fn lerp_point(from: [f32; 2], to: [f32; 2], alpha: f32) -> [f32; 2] {
+    [
+        from[0] + (to[0] - from[0]) * alpha,
+        from[1] + (to[1] - from[1]) * alpha,
+    ]
+}
+
+/// Blends two angles along the shortest way around the circle.
+fn lerp_angle(from: f32, to: f32, alpha: f32) -> f32 {
+    let mut difference = (to - from) % (2. * PI);
+    if difference > PI {
+        difference -= 2. * PI;
+    } else if difference < -PI {
+        difference += 2. * PI;
+    }
+    from + difference * alpha
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::float_eq;
+
+    use super::*;
+    use crate::state::Player;
+
+    #[test]
+    fn nothing_to_sample_before_the_first_snapshot() {
+        let mut interpolation = Interpolation::new();
+        interpolation.advance(1.);
+
+        assert_eq!(interpolation.sample(), None);
+    }
+
+    #[test]
+    fn single_snapshot_is_sampled_as_is() {
+        let mut interpolation = Interpolation::new();
+        interpolation.push(snapshot(10, 100.));
+
+        assert_eq!(interpolation.sample(), Some(snapshot(10, 100.)));
+    }
+
+    #[test]
+    fn samples_between_the_two_surrounding_snapshots() {
+        let mut interpolation = Interpolation::new();
+        interpolation.push(snapshot(10, 100.));
+        interpolation.push(snapshot(20, 200.));
+        interpolation.push(snapshot(30, 300.));
+
+        // render time starts INTERPOLATION_DELAY (6 ticks) behind the first snapshot
+        interpolation.advance(21. / TICK_RATE as f32);
+        let sample = interpolation.sample().unwrap();
+
+        assert!(float_eq!(sample.tanks[0].position[0], 250., abs <= 0.01));
+        assert!(float_eq!(
+            sample.tanks[0].tank_rotation,
+            0.625,
+            abs <= 0.0001
+        ));
+        assert!(float_eq!(sample.bullets[0].position[1], 250., abs <= 0.01));
+        assert_eq!(interpolation.buffered(), 2);
+    }
+
+    #[test]
+    fn extrapolates_for_a_limited_time_when_snapshots_stop() {
+        let mut interpolation = Interpolation::new();
+        interpolation.push(snapshot(10, 100.));
+        interpolation.push(snapshot(20, 200.));
+
+        interpolation.advance(21. / TICK_RATE as f32);
+        let extrapolated = interpolation.sample().unwrap();
+        interpolation.advance(10.);
+        let frozen = interpolation.sample().unwrap();
+
+        assert!(float_eq!(
+            extrapolated.tanks[0].position[0],
+            250.,
+            abs <= 0.01
+        ));
+        // MAX_EXTRAPOLATION is 15 ticks past the newest snapshot
+        assert!(float_eq!(frozen.tanks[0].position[0], 350., abs <= 0.01));
+        interpolation.advance(1.);
+        assert_eq!(interpolation.sample(), Some(frozen));
+    }
+
+    #[test]
+    fn catches_up_after_falling_behind() {
+        let mut interpolation = Interpolation::new();
+        interpolation.push(snapshot(10, 100.));
+        interpolation.push(snapshot(100, 1000.));
+
+        interpolation.advance(0.);
+        let sample = interpolation.sample().unwrap();
+
+        assert!(float_eq!(sample.tanks[0].position[0], 940., abs <= 0.01));
+    }
+
+    #[test]
+    fn older_snapshots_are_ignored() {
+        let mut interpolation = Interpolation::new();
+        interpolation.push(snapshot(10, 100.));
+        interpolation.push(snapshot(5, 50.));
+        interpolation.push(snapshot(10, 50.));
+
+        assert_eq!(interpolation.buffered(), 1);
+        assert_eq!(interpolation.sample(), Some(snapshot(10, 100.)));
+    }
+
+    #[test]
+    fn objects_only_in_the_newer_snapshot_are_not_blended() {
+        let mut interpolation = Interpolation::new();
+        interpolation.push(Snapshot {
+            tanks: vec![],
+            ..snapshot(10, 100.)
+        });
+        interpolation.push(snapshot(20, 200.));
+
+        interpolation.advance(11. / TICK_RATE as f32);
+        let sample = interpolation.sample().unwrap();
+
+        assert_eq!(sample.tanks[0].position, [200., 200.]);
+    }
+
+    #[test]
+    fn bullets_of_one_tank_are_blended_with_their_own_earlier_position() {
+        let mut interpolation = Interpolation::new();
+        let bullet = |x: f32| BulletSnapshot {
+            owner: Player::P2,
+            position: [x, 0.],
+            direction: [1., 0.],
+            rotation: 0.,
+        };
+        interpolation.push(Snapshot {
+            bullets: vec![bullet(100.), bullet(200.)],
+            ..snapshot(10, 100.)
+        });
+        interpolation.push(Snapshot {
+            bullets: vec![bullet(110.), bullet(210.)],
+            ..snapshot(20, 200.)
+        });
+
+        interpolation.advance(11. / TICK_RATE as f32);
+        let sample = interpolation.sample().unwrap();
+
+        assert!(float_eq!(sample.bullets[0].position[0], 105., abs <= 0.01));
+        assert!(float_eq!(sample.bullets[1].position[0], 205., abs <= 0.01));
+    }
+
+    #[test]
+    fn lerp_snapshot_blends_between_two_states() {
+        let blended = lerp_snapshot(&snapshot(10, 100.), &snapshot(11, 200.), 0.25);
+
+        assert_eq!(blended.tick, 11);
+        assert_eq!(blended.tanks[0].position, [125., 125.]);
+        assert_eq!(blended.bullets[0].position, [125., 125.]);
+    }
+
+    #[test]
+    fn angles_take_the_shortest_way_around() {
+        let angle = lerp_angle(PI - 0.1, -PI + 0.1, 0.5);
+
+        assert!(float_eq!(angle.abs(), PI, abs <= 0.0001));
+    }
+
+    fn snapshot(tick: u64, position: f32) -> Snapshot {
+        Snapshot {
+            tick,
+            tanks: vec![TankSnapshot {
+                player: Player::P2,
+                position: [position, position],
+                tank_rotation: position / 400.,
+                turret_rotation: 0.,
+                health: 100,
+                last_input: 0,
+            }],
+            bullets: vec![BulletSnapshot {
+                owner: Player::P2,
+                position: [position, position],
+                direction: [1., 0.],
+                rotation: 0.,
+            }],
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::map::Map;
+use crate::network::server::Server;
+
+/// A `Server` ticking on a background thread of this process, so a match can
+/// be hosted (or tested) without starting the server binary. The server stops
+/// when this is dropped.
+pub struct LocalServer {
+    addr: SocketAddr,
+    running: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<io::Result<()>>>,
+}
+
+impl LocalServer {
+    /// A server whose matches are played in an open arena.
+    pub fn spawn<A: ToSocketAddrs>(addr: A) -> io::Result<LocalServer> {
+        LocalServer::run(Server::bind(addr)?)
+    }
+
+    /// A server whose matches are played on `map`.
+    pub fn with_map<A: ToSocketAddrs>(addr: A, map: Map) -> io::Result<LocalServer> {
+        LocalServer::run(Server::with_map(addr, map)?)
+    }
+
+    fn run(mut server: Server) -> io::Result<LocalServer> {
+        let addr = server.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        let handle = thread::spawn(move || server.run_while(&flag));
+
+        Ok(LocalServer {
+            addr,
+            running,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Where this process reaches the server, which works even when it
+    /// listens on every interface.
+    pub fn loopback_addr(&self) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, self.addr.port()))
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::map::TileMap;
+    use crate::network::protocol::{decode, encode, ClientMessage, ServerMessage};
+    use crate::network::MAX_DATAGRAM_SIZE;
+    use crate::state::Player;
+
+    #[test]
+    fn answers_joins_until_dropped() {
+        let server = LocalServer::spawn("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.local_addr()).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+
+        socket.send(&encode(&ClientMessage::Join).unwrap()).unwrap();
+        let len = socket.recv(&mut buffer).unwrap();
+
+        assert_eq!(
+            decode::<ServerMessage>(&buffer[..len]).unwrap(),
+            ServerMessage::Welcome {
+                player: Player::P1,
+                map: TileMap::default(),
+            }
+        );
+        drop(server);
+    }
+
+    #[test]
+    fn servers_on_every_interface_are_reached_over_loopback() {
+        let server = LocalServer::spawn("0.0.0.0:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.loopback_addr()).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+
+        socket.send(&encode(&ClientMessage::Join).unwrap()).unwrap();
+        let len = socket.recv(&mut buffer).unwrap();
+
+        assert_eq!(server.loopback_addr().ip(), Ipv4Addr::LOCALHOST);
+        assert_eq!(
+            decode::<ServerMessage>(&buffer[..len]).unwrap(),
+            ServerMessage::Welcome {
+                player: Player::P1,
+                map: TileMap::default(),
+            }
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::network::MAX_DATAGRAM_SIZE;
+
+struct InFlight {
+    deliver_at: Instant,
+    to_server: bool,
+    bytes: Vec<u8>,
+}
+
+/// UDP relay on the loopback interface that sits between one client and the
+/// server, delaying every datagram by `latency` and dropping a `packet_loss`
+/// fraction of them. Point a `Client` at `local_addr` and call `pump`
+/// regularly to move datagrams along.
+pub struct LossyLoopback {
+    front: UdpSocket,
+    back: UdpSocket,
+    client: Option<SocketAddr>,
+    latency: Duration,
+    packet_loss: f32,
+    seed: u64,
+    in_flight: Vec<InFlight>,
+}
+
+impl LossyLoopback {
+    pub fn new<A: ToSocketAddrs>(
+        server: A,
+        latency: Duration,
+        packet_loss: f32,
+        seed: u64,
+    ) -> io::Result<LossyLoopback> {
+        let front = UdpSocket::bind("127.0.0.1:0")?;
+        let back = UdpSocket::bind("127.0.0.1:0")?;
+        back.connect(server)?;
+        front.set_nonblocking(true)?;
+        back.set_nonblocking(true)?;
+
+        Ok(LossyLoopback {
+            front,
+            back,
+            client: None,
+            latency,
+            packet_loss,
+            // xorshift gets stuck on zero
+            seed: seed.max(1),
+            in_flight: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.front.local_addr()
+    }
+
+    pub fn pump(&mut self) -> io::Result<()> {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+
+        while let Some((len, addr)) = would_block(self.front.recv_from(&mut buffer))? {
+            self.client = Some(addr);
+            self.enqueue(true, &buffer[..len]);
+        }
+        while let Some(len) = would_block(self.back.recv(&mut buffer))? {
+            self.enqueue(false, &buffer[..len]);
+        }
+
+        let now = Instant::now();
+        let (due, waiting) = self
+            .in_flight
+            .drain(..)
+            .partition(|datagram| datagram.deliver_at <= now);
+        self.in_flight = waiting;
+
+        for datagram in due {
+            if datagram.to_server {
+                self.back.send(&datagram.bytes)?;
+            } else if let Some(client) = self.client {
+                self.front.send_to(&datagram.bytes, client)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn enqueue(&mut self, to_server: bool, bytes: &[u8]) {
+        if self.next_random() < self.packet_loss {
+            return;
+        }
+        self.in_flight.push(InFlight {
+            deliver_at: Instant::now() + self.latency,
+            to_server,
+            bytes: bytes.to_vec(),
+        });
+    }
+
+    fn next_random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed % 10_000) as f32 / 10_000.
+    }
+}
+
+fn would_block<T>(result: io::Result<T>) -> io::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e)
+            if e.kind() == io::ErrorKind::WouldBlock
+                || e.kind() == io::ErrorKind::ConnectionRefused
+                || e.kind() == io::ErrorKind::ConnectionReset =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn datagrams_are_delayed() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut link = LossyLoopback::new(
+            server.local_addr().unwrap(),
+            Duration::from_millis(50),
+            0.,
+            1,
+        )
+        .unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_nonblocking(true).unwrap();
+        let sent_at = Instant::now();
+
+        client
+            .send_to(&[1, 2, 3], link.local_addr().unwrap())
+            .unwrap();
+        link.pump().unwrap();
+        assert!(server.recv(&mut [0; 8]).is_err());
+
+        let mut buffer = [0; 8];
+        let len = loop {
+            link.pump().unwrap();
+            if let Ok(len) = server.recv(&mut buffer) {
+                break len;
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+
+        assert_eq!(&buffer[..len], &[1, 2, 3]);
+        assert!(sent_at.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn replies_go_back_to_the_client() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut link = LossyLoopback::new(
+            server.local_addr().unwrap(),
+            Duration::from_millis(0),
+            0.,
+            1,
+        )
+        .unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let mut buffer = [0; 8];
+
+        client.send_to(&[1], link.local_addr().unwrap()).unwrap();
+        link.pump().unwrap();
+        let (_, relay) = server.recv_from(&mut buffer).unwrap();
+        server.send_to(&[2], relay).unwrap();
+        link.pump().unwrap();
+        let len = client.recv(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..len], &[2]);
+    }
+
+    #[test]
+    fn packet_loss_drops_about_the_configured_fraction() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut link = LossyLoopback::new(
+            server.local_addr().unwrap(),
+            Duration::from_millis(0),
+            0.3,
+            7,
+        )
+        .unwrap();
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_nonblocking(true).unwrap();
+        let mut buffer = [0; 8];
+
+        // drained as it goes, so the server's receive buffer never overflows
+        let mut delivered = 0;
+        for sequence in 0..1000u16 {
+            client
+                .send_to(&sequence.to_le_bytes(), link.local_addr().unwrap())
+                .unwrap();
+            link.pump().unwrap();
+            while server.recv(&mut buffer).is_ok() {
+                delivered += 1;
+            }
+        }
+        let dropped = 1000 - delivered;
+
+        assert!(dropped > 250 && dropped < 350, "dropped {}", dropped);
+    }
+}
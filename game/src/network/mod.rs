@@ -0,0 +1,16 @@
+pub mod client;
+pub mod delta;
+pub mod interpolation;
+pub mod local;
+pub mod loopback;
+pub mod prediction;
+pub mod protocol;
+pub mod server;
+
+pub const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:7878";
+/// Where `--host` listens: every network interface, so others can connect.
+pub const HOST_ADDR: &str = "0.0.0.0:7878";
+pub const TICK_RATE: u32 = 60;
+/// Simulated time covered by a single `SequencedInput`.
+pub const INPUT_DELTA: f32 = 1. / TICK_RATE as f32;
+pub const MAX_DATAGRAM_SIZE: usize = 4096;
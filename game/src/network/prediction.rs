@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+use ggez::graphics::Rect;
+
+use crate::collision::Obb;
+use crate::network::protocol::{PlayerInput, SequencedInput, TankSnapshot};
+use crate::network::INPUT_DELTA;
+use crate::objects::{Tank, TANK_DIMENSIONS};
+
+/// Inputs kept while waiting for the server to acknowledge them.
+pub const MAX_PENDING_INPUTS: usize = 120;
+/// Most recent unacknowledged inputs resent with every input datagram.
+pub const REDUNDANT_INPUTS: usize = 32;
+
+/// Client-side prediction for the local tank.
+///
+/// Inputs are applied to the tank as soon as they are produced and kept in a
+/// sequence-numbered buffer. When a snapshot arrives, the tank is rewound to
+/// the authoritative state and the inputs the server hasn't seen yet are
+/// replayed on top of it; if that disagrees with what we predicted, the
+/// replayed state wins.
+pub struct Prediction {
+    next_sequence: u32,
+    pending: VecDeque<SequencedInput>,
+    corrections: u32,
+}
+
+impl Default for Prediction {
+    fn default() -> Self {
+        Prediction {
+            next_sequence: 1,
+            pending: VecDeque::new(),
+            corrections: 0,
+        }
+    }
+}
+
+impl Prediction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn predict(
+        &mut self,
+        tank: &mut Tank,
+        input: PlayerInput,
+        screen_coord: Rect,
+        others: &[Obb],
+    ) -> SequencedInput {
+        let sequenced = SequencedInput {
+            sequence: self.next_sequence,
+            input,
+        };
+        self.next_sequence += 1;
+
+        tank.apply_input(&input, screen_coord, TANK_DIMENSIONS, others, INPUT_DELTA);
+        self.pending.push_back(sequenced);
+        if self.pending.len() > MAX_PENDING_INPUTS {
+            self.pending.pop_front();
+        }
+        sequenced
+    }
+
+    /// Returns `true` when the prediction disagreed with the server and was corrected.
+    pub fn reconcile(
+        &mut self,
+        tank: &mut Tank,
+        authoritative: &TankSnapshot,
+        screen_coord: Rect,
+        others: &[Obb],
+    ) -> bool {
+        while let Some(input) = self.pending.front() {
+            if input.sequence > authoritative.last_input {
+                break;
+            }
+            self.pending.pop_front();
+        }
+
+        let mut replayed = tank.clone();
+        replayed.apply_snapshot(authoritative);
+        for pending in &self.pending {
+            replayed.apply_input(
+                &pending.input,
+                screen_coord,
+                TANK_DIMENSIONS,
+                others,
+                INPUT_DELTA,
+            );
+        }
+
+        if TankSnapshot::from_tank(tank).is_close_to(&TankSnapshot::from_tank(&replayed)) {
+            false
+        } else {
+            *tank = replayed;
+            self.corrections += 1;
+            true
+        }
+    }
+
+    pub fn unacknowledged(&self) -> Vec<SequencedInput> {
+        let skip = self.pending.len().saturating_sub(REDUNDANT_INPUTS);
+        self.pending.iter().skip(skip).copied().collect()
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn corrections(&self) -> u32 {
+        self.corrections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ggez::nalgebra as na;
+
+    use super::*;
+    use crate::state::Player;
+
+    #[test]
+    fn input_is_applied_immediately() {
+        let mut prediction = Prediction::new();
+        let mut tank = tank();
+
+        let first = prediction.predict(&mut tank, forward(), screen(), &[]);
+        let second = prediction.predict(&mut tank, forward(), screen(), &[]);
+
+        assert_eq!(tank.position, na::Point2::from([598., 450.]));
+        assert_eq!((first.sequence, second.sequence), (1, 2));
+        assert_eq!(prediction.pending(), 2);
+    }
+
+    #[test]
+    fn acknowledged_inputs_are_dropped_without_correction() {
+        let mut prediction = Prediction::new();
+        let mut tank = tank();
+        for _ in 0..3 {
+            prediction.predict(&mut tank, forward(), screen(), &[]);
+        }
+
+        let corrected =
+            prediction.reconcile(&mut tank, &authoritative([599., 450.], 1), screen(), &[]);
+
+        assert!(!corrected);
+        assert_eq!(prediction.pending(), 2);
+        assert_eq!(tank.position, na::Point2::from([597., 450.]));
+        assert_eq!(prediction.corrections(), 0);
+    }
+
+    #[test]
+    fn disagreement_rewinds_and_replays_pending_inputs() {
+        let mut prediction = Prediction::new();
+        let mut tank = tank();
+        for _ in 0..3 {
+            prediction.predict(&mut tank, forward(), screen(), &[]);
+        }
+
+        // the server saw something blocking us: the first input didn't move the tank
+        let corrected =
+            prediction.reconcile(&mut tank, &authoritative([600., 450.], 1), screen(), &[]);
+
+        assert!(corrected);
+        assert_eq!(tank.position, na::Point2::from([598., 450.]));
+        assert_eq!(prediction.corrections(), 1);
+    }
+
+    #[test]
+    fn only_recent_inputs_are_resent() {
+        let mut prediction = Prediction::new();
+        let mut tank = tank();
+        for _ in 0..(REDUNDANT_INPUTS + 5) {
+            prediction.predict(&mut tank, forward(), screen(), &[]);
+        }
+
+        let resent = prediction.unacknowledged();
+
+        assert_eq!(resent.len(), REDUNDANT_INPUTS);
+        assert_eq!(resent[0].sequence, 6);
+        assert_eq!(
+            resent.last().map(|input| input.sequence),
+            Some(REDUNDANT_INPUTS as u32 + 5)
+        );
+    }
+
+    #[test]
+    fn pending_buffer_is_bounded() {
+        let mut prediction = Prediction::new();
+        let mut tank = tank();
+        for _ in 0..(MAX_PENDING_INPUTS + 10) {
+            prediction.predict(&mut tank, PlayerInput::default(), screen(), &[]);
+        }
+
+        assert_eq!(prediction.pending(), MAX_PENDING_INPUTS);
+    }
+
+    fn tank() -> Tank {
+        Tank::headless(Player::P1, na::Point2::from([600., 450.]))
+    }
+
+    fn forward() -> PlayerInput {
+        PlayerInput {
+            forward: true,
+            aim: [0., 450.],
+            ..PlayerInput::default()
+        }
+    }
+
+    fn authoritative(position: [f32; 2], last_input: u32) -> TankSnapshot {
+        TankSnapshot {
+            position,
+            last_input,
+            ..TankSnapshot::from_tank(&tank())
+        }
+    }
+
+    fn screen() -> Rect {
+        Rect::new(0., 0., 1200., 900.)
+    }
+}
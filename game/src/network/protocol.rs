@@ -0,0 +1,435 @@
+use std::io;
+
+use bincode::Options;
+use ggez::nalgebra as na;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::map::TileMap;
+use crate::network::delta::SnapshotDelta;
+use crate::network::MAX_DATAGRAM_SIZE;
+
+use crate::objects::bullet::Bullet;
+use crate::objects::Tank;
+use crate::state::Player;
+
+/// First byte of every datagram. Appending variants to the message enums
+/// keeps older peers working; anything else needs a new version and a
+/// `Message::decode_older` that reads the previous layout.
+pub const PROTOCOL_VERSION: u8 = 3;
+/// Oldest version this build still decodes.
+pub const MIN_PROTOCOL_VERSION: u8 = 2;
+
+/// A value sent as a whole datagram.
+pub trait Message: Serialize + DeserializeOwned {
+    /// Reads a payload written by an older version, which only differs
+    /// from the current layout if the type overrides this.
+    fn decode_older(_version: u8, payload: &[u8]) -> bincode::Result<Self> {
+        options().deserialize(payload)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Join,
+    /// Every input the server hasn't acknowledged yet, oldest first, so a
+    /// lost datagram is covered by the next one.
+    Input(Vec<SequencedInput>),
+    Leave,
+    /// Newest snapshot the client has, so the server can send deltas against it.
+    Ack {
+        tick: u64,
+    },
+    /// Sent repeatedly while in the lobby; the match starts once every player is ready.
+    Ready(bool),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    /// The slot the client plays in and the tiles of the arena, which it
+    /// draws and drives its predicted tank against.
+    Welcome {
+        player: Player,
+        map: TileMap,
+    },
+    Full,
+    Snapshot(Snapshot),
+    Delta(SnapshotDelta),
+    Lobby(Lobby),
+    MatchInProgress,
+}
+
+/// `ServerMessage` as version 2 wrote it: servers only played in an open
+/// arena, so `Welcome` carried no map.
+#[derive(Deserialize)]
+enum ServerMessageV2 {
+    Welcome { player: Player },
+    Full,
+    Snapshot(Snapshot),
+    Delta(SnapshotDelta),
+    Lobby(Lobby),
+    MatchInProgress,
+}
+
+impl Message for ClientMessage {}
+
+impl Message for ServerMessage {
+    fn decode_older(_version: u8, payload: &[u8]) -> bincode::Result<Self> {
+        Ok(match options().deserialize(payload)? {
+            ServerMessageV2::Welcome { player } => ServerMessage::Welcome {
+                player,
+                map: TileMap::default(),
+            },
+            ServerMessageV2::Full => ServerMessage::Full,
+            ServerMessageV2::Snapshot(snapshot) => ServerMessage::Snapshot(snapshot),
+            ServerMessageV2::Delta(delta) => ServerMessage::Delta(delta),
+            ServerMessageV2::Lobby(lobby) => ServerMessage::Lobby(lobby),
+            ServerMessageV2::MatchInProgress => ServerMessage::MatchInProgress,
+        })
+    }
+}
+
+impl Message for Snapshot {}
+
+/// Players waiting for the match to start, ordered by slot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Lobby {
+    pub players: Vec<LobbySlot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LobbySlot {
+    pub player: Player,
+    pub ready: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayerInput {
+    pub forward: bool,
+    pub backward: bool,
+    pub turn_left: bool,
+    pub turn_right: bool,
+    pub fire: bool,
+    pub aim: [f32; 2],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SequencedInput {
+    pub sequence: u32,
+    pub input: PlayerInput,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub tanks: Vec<TankSnapshot>,
+    pub bullets: Vec<BulletSnapshot>,
+}
+
+/// Simulation state of a `Tank`, without its textures.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TankSnapshot {
+    pub player: Player,
+    pub position: [f32; 2],
+    pub tank_rotation: f32,
+    pub turret_rotation: f32,
+    pub health: u32,
+    /// Sequence of the last input the server applied to this tank.
+    pub last_input: u32,
+}
+
+/// Simulation state of a `Bullet`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BulletSnapshot {
+    pub owner: Player,
+    pub position: [f32; 2],
+    pub direction: [f32; 2],
+    pub rotation: f32,
+}
+
+impl PlayerInput {
+    pub fn aim(&self) -> na::Point2<f32> {
+        na::Point2::from(self.aim)
+    }
+}
+
+impl TankSnapshot {
+    pub fn from_tank(tank: &Tank) -> Self {
+        TankSnapshot {
+            player: tank.player,
+            position: [tank.position.x, tank.position.y],
+            tank_rotation: tank.tank_rotation,
+            turret_rotation: tank.turret_rotation,
+            health: tank.health,
+            last_input: 0,
+        }
+    }
+
+    pub fn is_close_to(&self, other: &TankSnapshot) -> bool {
+        const TOLERANCE: f32 = 0.01;
+
+        (self.position[0] - other.position[0]).abs() <= TOLERANCE
+            && (self.position[1] - other.position[1]).abs() <= TOLERANCE
+            && (self.tank_rotation - other.tank_rotation).abs() <= TOLERANCE
+            && (self.turret_rotation - other.turret_rotation).abs() <= TOLERANCE
+    }
+}
+
+impl BulletSnapshot {
+    pub fn from_bullet(bullet: &Bullet) -> Self {
+        BulletSnapshot {
+            owner: bullet.owner,
+            position: [bullet.position.x, bullet.position.y],
+            direction: [bullet.direction.x, bullet.direction.y],
+            rotation: bullet.rotation,
+        }
+    }
+
+    pub fn to_bullet(&self) -> Bullet {
+        Bullet {
+            position: na::Point2::from(self.position),
+            direction: na::Vector2::from(self.direction),
+            rotation: self.rotation,
+            owner: self.owner,
+        }
+    }
+}
+
+pub fn encode<T: Message>(message: &T) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![PROTOCOL_VERSION];
+    options()
+        .serialize_into(&mut bytes, message)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(bytes)
+}
+
+pub fn decode<T: Message>(bytes: &[u8]) -> io::Result<T> {
+    let message = match bytes.split_first() {
+        Some((&PROTOCOL_VERSION, payload)) => options().deserialize(payload),
+        Some((&version, payload))
+            if (MIN_PROTOCOL_VERSION..PROTOCOL_VERSION).contains(&version) =>
+        {
+            T::decode_older(version, payload)
+        }
+        Some((version, _)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported protocol version {}", version),
+            ))
+        }
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "empty datagram")),
+    };
+    message.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Little endian with variable length integers, so small ticks and sequences stay small.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(MAX_DATAGRAM_SIZE as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_message_round_trip() {
+        let message = ClientMessage::Input(vec![SequencedInput {
+            sequence: 7,
+            input: PlayerInput {
+                forward: true,
+                fire: true,
+                aim: [10., 20.],
+                ..PlayerInput::default()
+            },
+        }]);
+
+        let bytes = encode(&message).unwrap();
+
+        assert_eq!(decode::<ClientMessage>(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let message = ServerMessage::Snapshot(Snapshot {
+            tick: 42,
+            tanks: vec![TankSnapshot {
+                player: Player::P3,
+                position: [300., 400.],
+                tank_rotation: 0.5,
+                turret_rotation: -1.5,
+                health: 75,
+                last_input: 3,
+            }],
+            bullets: vec![BulletSnapshot {
+                owner: Player::P3,
+                position: [310., 410.],
+                direction: [1., 0.],
+                rotation: 0.,
+            }],
+        });
+
+        let bytes = encode(&message).unwrap();
+
+        assert_eq!(decode::<ServerMessage>(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn lobby_round_trip() {
+        let message = ServerMessage::Lobby(Lobby {
+            players: vec![
+                LobbySlot {
+                    player: Player::P1,
+                    ready: true,
+                },
+                LobbySlot {
+                    player: Player::P2,
+                    ready: false,
+                },
+            ],
+        });
+
+        let bytes = encode(&message).unwrap();
+
+        assert_eq!(decode::<ServerMessage>(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn garbage_is_invalid_data() {
+        let err = decode::<ServerMessage>(&[255, 255, 255, 255]).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn datagrams_start_with_the_protocol_version() {
+        let bytes = encode(&ClientMessage::Join).unwrap();
+
+        assert_eq!(bytes, vec![PROTOCOL_VERSION, 0]);
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let mut bytes = encode(&ClientMessage::Join).unwrap();
+        bytes[0] = PROTOCOL_VERSION + 1;
+
+        let err = decode::<ClientMessage>(&bytes).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(decode::<ClientMessage>(&[]).is_err());
+    }
+
+    #[test]
+    fn datagram_layout_is_stable() {
+        // bytes the current encoder writes; if this test has to change, peers
+        // on the same `PROTOCOL_VERSION` can no longer talk, so bump it
+        let input = [3, 1, 1, 7, 1, 0, 0, 0, 1, 0, 0, 32, 65, 0, 0, 160, 65];
+        let snapshot = [
+            3, 2, 42, 1, 2, 0, 0, 150, 67, 0, 0, 200, 67, 0, 0, 0, 63, 0, 0, 192, 191, 75, 3, 0,
+        ];
+        let welcome = [3, 0, 1, 3, b'#', b'.', b'\n'];
+
+        assert_eq!(
+            decode::<ClientMessage>(&input).unwrap(),
+            ClientMessage::Input(vec![SequencedInput {
+                sequence: 7,
+                input: PlayerInput {
+                    forward: true,
+                    fire: true,
+                    aim: [10., 20.],
+                    ..PlayerInput::default()
+                },
+            }])
+        );
+        assert_eq!(
+            decode::<ServerMessage>(&snapshot).unwrap(),
+            ServerMessage::Snapshot(Snapshot {
+                tick: 42,
+                tanks: vec![TankSnapshot {
+                    player: Player::P3,
+                    position: [300., 400.],
+                    tank_rotation: 0.5,
+                    turret_rotation: -1.5,
+                    health: 75,
+                    last_input: 3,
+                }],
+                bullets: vec![],
+            })
+        );
+        assert_eq!(
+            decode::<ServerMessage>(&welcome).unwrap(),
+            ServerMessage::Welcome {
+                player: Player::P2,
+                map: "#.".parse().unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn version_2_datagrams_still_decode() {
+        // bytes the version 2 encoder wrote, before `Welcome` carried the map
+        let input = [2, 1, 1, 7, 1, 0, 0, 0, 1, 0, 0, 32, 65, 0, 0, 160, 65];
+        let snapshot = [
+            2, 2, 42, 1, 2, 0, 0, 150, 67, 0, 0, 200, 67, 0, 0, 0, 63, 0, 0, 192, 191, 75, 3, 0,
+        ];
+        let welcome = [2, 0, 1];
+
+        assert_eq!(
+            decode::<ClientMessage>(&input).unwrap(),
+            ClientMessage::Input(vec![SequencedInput {
+                sequence: 7,
+                input: PlayerInput {
+                    forward: true,
+                    fire: true,
+                    aim: [10., 20.],
+                    ..PlayerInput::default()
+                },
+            }])
+        );
+        assert_eq!(
+            decode::<ServerMessage>(&snapshot).unwrap(),
+            ServerMessage::Snapshot(Snapshot {
+                tick: 42,
+                tanks: vec![TankSnapshot {
+                    player: Player::P3,
+                    position: [300., 400.],
+                    tank_rotation: 0.5,
+                    turret_rotation: -1.5,
+                    health: 75,
+                    last_input: 3,
+                }],
+                bullets: vec![],
+            })
+        );
+        assert_eq!(
+            decode::<ServerMessage>(&welcome).unwrap(),
+            ServerMessage::Welcome {
+                player: Player::P2,
+                map: TileMap::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn tank_snapshots_within_tolerance_are_close() {
+        let snapshot = TankSnapshot {
+            player: Player::P1,
+            position: [300., 400.],
+            tank_rotation: 0.5,
+            turret_rotation: -1.5,
+            health: 75,
+            last_input: 3,
+        };
+        let nudged = TankSnapshot {
+            position: [300.005, 400.],
+            last_input: 4,
+            ..snapshot
+        };
+        let moved = TankSnapshot {
+            position: [301., 400.],
+            ..snapshot
+        };
+
+        assert!(snapshot.is_close_to(&nudged));
+        assert!(!snapshot.is_close_to(&moved));
+    }
+}
@@ -0,0 +1,891 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::map::Map;
+use crate::network::protocol::{
+    decode, encode, ClientMessage, Lobby, LobbySlot, SequencedInput, ServerMessage, Snapshot,
+};
+use crate::network::{INPUT_DELTA, MAX_DATAGRAM_SIZE, TICK_RATE};
+use crate::simulation::World;
+use crate::state::Player;
+
+/// Clients that stay silent for longer than this lose their tank.
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Snapshots kept around as bases for delta compression.
+pub const SNAPSHOT_HISTORY: usize = 64;
+/// Inputs waiting for their tick, per client. Older ones are dropped when a
+/// client sends more, so a burst can't build up lag or move a tank faster.
+pub const MAX_QUEUED_INPUTS: usize = 8;
+
+struct Connection {
+    player: Player,
+    inputs: VecDeque<SequencedInput>,
+    last_input: u32,
+    last_seen: Instant,
+    acked_tick: Option<u64>,
+    ready: bool,
+}
+
+impl Connection {
+    fn newest_input(&self) -> u32 {
+        self.inputs
+            .back()
+            .map_or(self.last_input, |input| input.sequence)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Players join and mark themselves ready, nothing is simulated.
+    Lobby,
+    Playing,
+}
+
+/// Authoritative game server. Clients first gather in a lobby; once every
+/// joined player is ready a match starts with one tank each, and from then on
+/// the server applies the inputs sent by clients and broadcasts the resulting
+/// snapshot each tick. When the last player leaves it goes back to the lobby.
+pub struct Server {
+    socket: UdpSocket,
+    connections: HashMap<SocketAddr, Connection>,
+    /// What every match is played on.
+    map: Map,
+    world: World,
+    tick: u64,
+    history: VecDeque<Snapshot>,
+    phase: Phase,
+}
+
+impl Server {
+    /// A server whose matches are played in an open arena.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Server> {
+        Server::with_map(addr, Map::default())
+    }
+
+    /// A server whose matches are played on `map`. Joining clients are sent
+    /// its tiles, so they have to fit in one datagram.
+    pub fn with_map<A: ToSocketAddrs>(addr: A, map: Map) -> io::Result<Server> {
+        let welcome = ServerMessage::Welcome {
+            player: Player::P1,
+            map: map.tiles.clone(),
+        };
+        encode(&welcome).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the map is too large to send to clients",
+            )
+        })?;
+
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Server {
+            socket,
+            connections: HashMap::new(),
+            world: World::with_map(&[], map.tiles.arena(), map.clone()),
+            map,
+            tick: 0,
+            history: VecDeque::new(),
+            phase: Phase::Lobby,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Joined players, ordered by slot.
+    pub fn players(&self) -> Vec<Player> {
+        let mut players: Vec<Player> = self
+            .connections
+            .values()
+            .map(|connection| connection.player)
+            .collect();
+        players.sort();
+        players
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        self.run_while(&AtomicBool::new(true))
+    }
+
+    /// Ticks at `TICK_RATE` until `running` is cleared.
+    pub fn run_while(&mut self, running: &AtomicBool) -> io::Result<()> {
+        let step = Duration::from_secs(1) / TICK_RATE;
+        while running.load(Ordering::SeqCst) {
+            let start = Instant::now();
+            self.tick()?;
+            if let Some(remaining) = step.checked_sub(start.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tick(&mut self) -> io::Result<()> {
+        self.receive()?;
+        self.drop_idle_clients();
+        match self.phase {
+            Phase::Lobby => {
+                self.start_match_when_ready();
+                if self.phase == Phase::Lobby {
+                    return self.broadcast_lobby();
+                }
+            }
+            Phase::Playing if self.connections.is_empty() => {
+                self.end_match();
+                return Ok(());
+            }
+            Phase::Playing => self.step(),
+        }
+        self.broadcast()
+    }
+
+    pub fn lobby(&self) -> Lobby {
+        let mut players: Vec<LobbySlot> = self
+            .connections
+            .values()
+            .map(|connection| LobbySlot {
+                player: connection.player,
+                ready: connection.ready,
+            })
+            .collect();
+        players.sort_by_key(|slot| slot.player);
+        Lobby { players }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = self.world.snapshot(self.tick);
+        for tank in &mut snapshot.tanks {
+            tank.last_input = self
+                .connections
+                .values()
+                .find(|connection| connection.player == tank.player)
+                .map_or(0, |connection| connection.last_input);
+        }
+        snapshot
+    }
+
+    fn receive(&mut self) -> io::Result<()> {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, addr)) => {
+                    // malformed datagrams are dropped, UDP gives no guarantees anyway
+                    if let Ok(message) = decode(&buffer[..len]) {
+                        self.handle(addr, message)?;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn handle(&mut self, addr: SocketAddr, message: ClientMessage) -> io::Result<()> {
+        match message {
+            ClientMessage::Join => {
+                let reply = match self.connections.get(&addr) {
+                    Some(connection) => ServerMessage::Welcome {
+                        player: connection.player,
+                        map: self.map.tiles.clone(),
+                    },
+                    None if self.phase == Phase::Playing => ServerMessage::MatchInProgress,
+                    None => match self.join(addr) {
+                        Some(player) => ServerMessage::Welcome {
+                            player,
+                            map: self.map.tiles.clone(),
+                        },
+                        None => ServerMessage::Full,
+                    },
+                };
+                if !deliver(&self.socket, &encode(&reply)?, addr) {
+                    self.leave(addr);
+                }
+            }
+            ClientMessage::Input(inputs) => {
+                if let Some(connection) = self.connections.get_mut(&addr) {
+                    for input in inputs {
+                        if input.sequence > connection.newest_input() {
+                            connection.inputs.push_back(input);
+                        }
+                    }
+                    while connection.inputs.len() > MAX_QUEUED_INPUTS {
+                        connection.inputs.pop_front();
+                    }
+                    connection.last_seen = Instant::now();
+                }
+            }
+            ClientMessage::Leave => self.leave(addr),
+            ClientMessage::Ack { tick } => {
+                if let Some(connection) = self.connections.get_mut(&addr) {
+                    connection.acked_tick = connection.acked_tick.max(Some(tick));
+                    connection.last_seen = Instant::now();
+                }
+            }
+            ClientMessage::Ready(ready) => {
+                if let Some(connection) = self.connections.get_mut(&addr) {
+                    connection.ready = ready;
+                    connection.last_seen = Instant::now();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn join(&mut self, addr: SocketAddr) -> Option<Player> {
+        let player = Player::ALL.iter().copied().find(|player| {
+            self.connections
+                .values()
+                .all(|connection| connection.player != *player)
+        })?;
+
+        self.connections.insert(
+            addr,
+            Connection {
+                player,
+                inputs: VecDeque::new(),
+                last_input: 0,
+                last_seen: Instant::now(),
+                acked_tick: None,
+                ready: false,
+            },
+        );
+        Some(player)
+    }
+
+    fn start_match_when_ready(&mut self) {
+        if self.connections.is_empty() || !self.connections.values().all(|c| c.ready) {
+            return;
+        }
+
+        self.world = World::with_map(&self.players(), self.world.coordinate, self.map.clone());
+        self.phase = Phase::Playing;
+    }
+
+    fn end_match(&mut self) {
+        self.world.tanks.clear();
+        self.history.clear();
+        self.phase = Phase::Lobby;
+    }
+
+    fn leave(&mut self, addr: SocketAddr) {
+        if let Some(connection) = self.connections.remove(&addr) {
+            self.world
+                .tanks
+                .retain(|tank| tank.player != connection.player);
+        }
+    }
+
+    fn drop_idle_clients(&mut self) {
+        let idle: Vec<SocketAddr> = self
+            .connections
+            .iter()
+            .filter(|(_, connection)| connection.last_seen.elapsed() > CLIENT_TIMEOUT)
+            .map(|(addr, _)| *addr)
+            .collect();
+
+        for addr in idle {
+            self.leave(addr);
+        }
+    }
+
+    fn step(&mut self) {
+        self.tick += 1;
+
+        // each input covers one tick, so a tank never moves faster than its
+        // speed however many its client sends
+        for connection in self.connections.values_mut() {
+            if let Some(sequenced) = connection.inputs.pop_front() {
+                if self
+                    .world
+                    .apply_input(connection.player, &sequenced.input, INPUT_DELTA)
+                {
+                    connection.last_input = sequenced.sequence;
+                } else {
+                    // destroyed, nothing left to drive
+                    connection.inputs.clear();
+                }
+            }
+        }
+        self.world.advance(INPUT_DELTA);
+    }
+
+    fn broadcast_lobby(&mut self) -> io::Result<()> {
+        let bytes = encode(&ServerMessage::Lobby(self.lobby()))?;
+        let unreachable: Vec<SocketAddr> = self
+            .connections
+            .keys()
+            .filter(|addr| !deliver(&self.socket, &bytes, **addr))
+            .copied()
+            .collect();
+
+        for addr in unreachable {
+            self.leave(addr);
+        }
+        Ok(())
+    }
+
+    /// Sends every client the changes since the last snapshot it acknowledged,
+    /// or the full snapshot when that one is no longer in the history.
+    fn broadcast(&mut self) -> io::Result<()> {
+        let snapshot = self.snapshot();
+        let full = encode(&ServerMessage::Snapshot(snapshot.clone()))?;
+
+        let mut unreachable = Vec::new();
+        for (addr, connection) in &self.connections {
+            let base = connection
+                .acked_tick
+                .and_then(|tick| self.history.iter().find(|base| base.tick == tick));
+            let delivered = match base {
+                Some(base) => {
+                    let delta = ServerMessage::Delta(snapshot.delta_from(base));
+                    deliver(&self.socket, &encode(&delta)?, *addr)
+                }
+                None => deliver(&self.socket, &full, *addr),
+            };
+            if !delivered {
+                unreachable.push(*addr);
+            }
+        }
+        for addr in unreachable {
+            self.leave(addr);
+        }
+
+        self.history.push_back(snapshot);
+        if self.history.len() > SNAPSHOT_HISTORY {
+            self.history.pop_front();
+        }
+        Ok(())
+    }
+}
+
+/// Sends one datagram to a client, returning false when it can't be
+/// reached. A failed send only concerns that client, so the caller drops it
+/// like an idle one; a full send buffer just loses the datagram.
+fn deliver(socket: &UdpSocket, bytes: &[u8], addr: SocketAddr) -> bool {
+    match socket.send_to(bytes, addr) {
+        Ok(_) => true,
+        Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ggez::nalgebra as na;
+
+    use super::*;
+    use crate::map::{Tile, TileMap};
+    use crate::network::protocol::PlayerInput;
+    use crate::objects::bullet::{Bullet, DAMAGE};
+    use crate::objects::{MAX_HEALTH, TANK_SPEED};
+
+    #[test]
+    fn players_get_free_slots_until_server_is_full() {
+        let mut server = server();
+        let sockets: Vec<UdpSocket> = (0..5).map(|_| socket(&server)).collect();
+
+        let replies: Vec<ServerMessage> = sockets
+            .iter()
+            .map(|socket| {
+                send(socket, &ClientMessage::Join);
+                server.tick().unwrap();
+                recv(socket)
+            })
+            .collect();
+
+        assert_eq!(
+            replies,
+            vec![
+                welcome(Player::P1),
+                welcome(Player::P2),
+                welcome(Player::P3),
+                welcome(Player::P4),
+                ServerMessage::Full,
+            ]
+        );
+        assert_eq!(server.players(), Player::ALL.to_vec());
+    }
+
+    #[test]
+    fn joining_twice_keeps_the_same_slot() {
+        let mut server = server();
+        let socket = socket(&server);
+
+        send(&socket, &ClientMessage::Join);
+        send(&socket, &ClientMessage::Join);
+        server.tick().unwrap();
+
+        assert_eq!(recv(&socket), welcome(Player::P1));
+        assert_eq!(recv(&socket), welcome(Player::P1));
+        assert_eq!(server.players(), vec![Player::P1]);
+    }
+
+    #[test]
+    fn leaving_frees_the_slot() {
+        let mut server = server();
+        let first = join(&mut server);
+        let _second = join(&mut server);
+
+        send(&first, &ClientMessage::Leave);
+        server.tick().unwrap();
+        let third = socket(&server);
+        send(&third, &ClientMessage::Join);
+        server.tick().unwrap();
+
+        assert_eq!(server.players(), vec![Player::P1, Player::P2]);
+        assert_eq!(recv_welcome(&third), welcome(Player::P1));
+    }
+
+    #[test]
+    fn input_drives_the_senders_tank() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        let p2 = join(&mut server);
+        start(&mut server, &[&p1, &p2]);
+
+        send(&p1, &inputs(1..=1, forward()));
+        server.tick().unwrap();
+        let snapshot = latest_snapshot(&p1);
+
+        assert_eq!(snapshot.tanks.len(), 2);
+        assert_eq!(snapshot.tanks[0].player, Player::P1);
+        assert_eq!(snapshot.tanks[0].position, [1049., 450.]);
+        assert_eq!(snapshot.tanks[0].last_input, 1);
+        assert_eq!(snapshot.tanks[1].player, Player::P2);
+        assert_eq!(snapshot.tanks[1].position, [150., 450.]);
+        assert_eq!(snapshot.tanks[1].last_input, 0);
+    }
+
+    #[test]
+    fn resent_inputs_are_applied_once() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        start(&mut server, &[&p1]);
+
+        send(&p1, &inputs(1..=2, forward()));
+        send(&p1, &inputs(1..=3, forward()));
+        server.tick().unwrap();
+        send(&p1, &inputs(2..=3, forward()));
+        for _ in 0..4 {
+            server.tick().unwrap();
+        }
+        let snapshot = latest_snapshot(&p1);
+
+        assert_eq!(snapshot.tanks[0].position, [597., 450.]);
+        assert_eq!(snapshot.tanks[0].last_input, 3);
+    }
+
+    #[test]
+    fn bursts_of_inputs_are_applied_one_per_tick() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        start(&mut server, &[&p1]);
+        let start = server.snapshot().tanks[0].position;
+
+        send(&p1, &inputs(1..=40, forward()));
+        for tick in 1..=3 {
+            server.tick().unwrap();
+            let position = latest_snapshot(&p1).tanks[0].position;
+
+            let moved = (position[0] - start[0]).hypot(position[1] - start[1]);
+            assert!(moved <= TANK_SPEED * INPUT_DELTA * tick as f32 + 0.001);
+        }
+        for _ in 0..MAX_QUEUED_INPUTS {
+            server.tick().unwrap();
+        }
+        let snapshot = latest_snapshot(&p1);
+
+        // the oldest of the burst were dropped rather than queued
+        assert_eq!(snapshot.tanks[0].last_input, 40);
+        assert_eq!(
+            snapshot.tanks[0].position,
+            [start[0] - MAX_QUEUED_INPUTS as f32, start[1]]
+        );
+    }
+
+    #[test]
+    fn snapshots_are_broadcast_to_every_client() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        let p2 = join(&mut server);
+        start(&mut server, &[&p1, &p2]);
+
+        server.tick().unwrap();
+
+        assert_eq!(latest_snapshot(&p1), server.snapshot());
+        assert_eq!(latest_snapshot(&p2), server.snapshot());
+    }
+
+    #[test]
+    fn fire_rate_limits_bullets_per_player() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        start(&mut server, &[&p1]);
+        let fire = PlayerInput {
+            fire: true,
+            ..PlayerInput::default()
+        };
+
+        send(&p1, &inputs(1..=1, fire));
+        server.tick().unwrap();
+        send(&p1, &inputs(2..=2, fire));
+        server.tick().unwrap();
+        let snapshot = latest_snapshot(&p1);
+
+        assert_eq!(snapshot.bullets.len(), 1);
+        assert_eq!(snapshot.bullets[0].owner, Player::P1);
+
+        for sequence in 3..=TICK_RATE {
+            send(&p1, &inputs(sequence..=sequence, fire));
+            server.tick().unwrap();
+        }
+
+        // a second at 4 shots per second
+        assert_eq!(latest_snapshot(&p1).bullets.len(), 4);
+    }
+
+    #[test]
+    fn bullets_leaving_the_arena_are_removed() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        start(&mut server, &[&p1]);
+        server.world.bullets.push(Bullet {
+            position: na::Point2::from([1., 1.]),
+            direction: na::Vector2::from([-1., 0.]),
+            rotation: 0.,
+            owner: Player::P1,
+        });
+
+        server.tick().unwrap();
+
+        assert!(latest_snapshot(&p1).bullets.is_empty());
+    }
+
+    #[test]
+    fn acknowledged_snapshots_are_the_base_of_deltas() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        start(&mut server, &[&p1]);
+        server.tick().unwrap();
+        let base = latest_snapshot(&p1);
+
+        send(&p1, &ClientMessage::Ack { tick: base.tick });
+        send(&p1, &inputs(1..=1, forward()));
+        server.tick().unwrap();
+        let delta = match recv(&p1) {
+            ServerMessage::Delta(delta) => delta,
+            message => panic!("expected a delta, got {:?}", message),
+        };
+
+        assert_eq!(delta.base_tick, base.tick);
+        assert_eq!(delta.apply_to(&base).unwrap(), server.snapshot());
+    }
+
+    #[test]
+    fn unknown_acks_fall_back_to_full_snapshots() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        start(&mut server, &[&p1]);
+
+        send(&p1, &ClientMessage::Ack { tick: 1_000 });
+        server.tick().unwrap();
+
+        assert_eq!(latest_snapshot(&p1), server.snapshot());
+    }
+
+    #[test]
+    fn bullets_damage_other_tanks_and_destroy_them() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        let p2 = join(&mut server);
+        start(&mut server, &[&p1, &p2]);
+        let target = server.world.tanks[1].position;
+
+        for _ in 0..(MAX_HEALTH / DAMAGE) {
+            server.world.bullets.push(Bullet {
+                position: target,
+                direction: na::Vector2::from([0., 0.]),
+                rotation: 0.,
+                owner: Player::P1,
+            });
+            server.tick().unwrap();
+        }
+        let snapshot = latest_snapshot(&p1);
+
+        assert!(snapshot.bullets.is_empty());
+        assert_eq!(snapshot.tanks.len(), 1);
+        assert_eq!(snapshot.tanks[0].player, Player::P1);
+    }
+
+    #[test]
+    fn lobby_lists_players_and_whether_they_are_ready() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        let _p2 = join(&mut server);
+
+        send(&p1, &ClientMessage::Ready(true));
+        server.tick().unwrap();
+
+        assert_eq!(
+            latest_lobby(&p1),
+            Lobby {
+                players: vec![
+                    LobbySlot {
+                        player: Player::P1,
+                        ready: true,
+                    },
+                    LobbySlot {
+                        player: Player::P2,
+                        ready: false,
+                    },
+                ],
+            }
+        );
+        assert_eq!(server.phase(), Phase::Lobby);
+        assert!(server.snapshot().tanks.is_empty());
+    }
+
+    #[test]
+    fn match_starts_with_every_joined_tank_once_all_are_ready() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        let p2 = join(&mut server);
+        let p3 = join(&mut server);
+
+        send(&p1, &ClientMessage::Ready(true));
+        send(&p2, &ClientMessage::Ready(true));
+        server.tick().unwrap();
+        assert_eq!(server.phase(), Phase::Lobby);
+
+        send(&p3, &ClientMessage::Ready(true));
+        server.tick().unwrap();
+        let snapshot = latest_snapshot(&p3);
+
+        assert_eq!(server.phase(), Phase::Playing);
+        assert_eq!(
+            snapshot
+                .tanks
+                .iter()
+                .map(|tank| tank.player)
+                .collect::<Vec<Player>>(),
+            vec![Player::P1, Player::P2, Player::P3]
+        );
+    }
+
+    #[test]
+    fn joining_a_running_match_is_refused() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        start(&mut server, &[&p1]);
+        let late = socket(&server);
+
+        send(&late, &ClientMessage::Join);
+        server.tick().unwrap();
+
+        assert_eq!(recv(&late), ServerMessage::MatchInProgress);
+        assert_eq!(server.players(), vec![Player::P1]);
+    }
+
+    #[test]
+    fn unreachable_clients_are_dropped_without_stopping_the_server() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        // broadcasts need SO_BROADCAST, so sending there fails with EACCES
+        let unreachable: SocketAddr = "255.255.255.255:7878".parse().unwrap();
+
+        server.handle(unreachable, ClientMessage::Join).unwrap();
+        assert_eq!(server.players(), vec![Player::P1]);
+
+        server.join(unreachable);
+        server.tick().unwrap();
+        assert_eq!(server.players(), vec![Player::P1]);
+
+        start(&mut server, &[&p1]);
+        server.join(unreachable);
+        server.tick().unwrap();
+        assert_eq!(server.players(), vec![Player::P1]);
+        assert_eq!(latest_snapshot(&p1).tanks.len(), 1);
+    }
+
+    #[test]
+    fn server_returns_to_the_lobby_when_everyone_leaves() {
+        let mut server = server();
+        let p1 = join(&mut server);
+        start(&mut server, &[&p1]);
+
+        send(&p1, &ClientMessage::Leave);
+        server.tick().unwrap();
+        let p2 = socket(&server);
+        send(&p2, &ClientMessage::Join);
+        server.tick().unwrap();
+
+        assert_eq!(server.phase(), Phase::Lobby);
+        assert_eq!(recv(&p2), welcome(Player::P1));
+        assert!(server.snapshot().tanks.is_empty());
+    }
+
+    #[test]
+    fn matches_are_played_on_the_servers_map() {
+        let map: Map = "size 12 6
+spawn P1 450 150
+spawn P2 150 150
+spawn P3 450 50
+spawn P4 150 250
+tiles
+............
+............
+.....#......
+.....#......
+............
+............"
+            .parse()
+            .unwrap();
+        let mut server = Server::with_map("127.0.0.1:0", map.clone()).unwrap();
+        let socket = socket(&server);
+
+        send(&socket, &ClientMessage::Join);
+        server.tick().unwrap();
+        assert_eq!(
+            recv_welcome(&socket),
+            ServerMessage::Welcome {
+                player: Player::P1,
+                map: map.tiles.clone(),
+            }
+        );
+        let p2 = join(&mut server);
+        start(&mut server, &[&socket, &p2]);
+
+        let snapshot = latest_snapshot(&socket);
+        assert_eq!(snapshot.tanks[0].position, [450., 150.]);
+        assert_eq!(snapshot.tanks[1].position, [150., 150.]);
+        assert_eq!(server.world.map.tile(5, 2), Tile::Wall);
+        assert_eq!(server.world.coordinate, map.tiles.bounds());
+    }
+
+    #[test]
+    fn maps_too_large_for_a_datagram_are_refused() {
+        let map = Map {
+            tiles: TileMap::new(80, 60),
+            ..Map::default()
+        };
+
+        let outpost: Map = include_str!("../../../resources/maps/outpost.map")
+            .parse()
+            .unwrap();
+
+        let refused = Server::with_map("127.0.0.1:0", map);
+
+        assert_eq!(
+            refused.err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidInput)
+        );
+        assert!(Server::with_map("127.0.0.1:0", outpost).is_ok());
+    }
+
+    fn server() -> Server {
+        Server::bind("127.0.0.1:0").unwrap()
+    }
+
+    fn welcome(player: Player) -> ServerMessage {
+        ServerMessage::Welcome {
+            player,
+            map: TileMap::default(),
+        }
+    }
+
+    fn socket(server: &Server) -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.local_addr().unwrap()).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        socket
+    }
+
+    fn join(server: &mut Server) -> UdpSocket {
+        let socket = socket(server);
+        send(&socket, &ClientMessage::Join);
+        server.tick().unwrap();
+        recv_welcome(&socket);
+        socket
+    }
+
+    fn start(server: &mut Server, sockets: &[&UdpSocket]) {
+        for socket in sockets {
+            send(socket, &ClientMessage::Ready(true));
+        }
+        server.tick().unwrap();
+        assert_eq!(server.phase(), Phase::Playing);
+    }
+
+    fn forward() -> PlayerInput {
+        PlayerInput {
+            forward: true,
+            aim: [0., 450.],
+            ..PlayerInput::default()
+        }
+    }
+
+    fn inputs(sequences: std::ops::RangeInclusive<u32>, input: PlayerInput) -> ClientMessage {
+        ClientMessage::Input(
+            sequences
+                .map(|sequence| SequencedInput { sequence, input })
+                .collect(),
+        )
+    }
+
+    fn send(socket: &UdpSocket, message: &ClientMessage) {
+        socket.send(&encode(message).unwrap()).unwrap();
+    }
+
+    fn recv(socket: &UdpSocket) -> ServerMessage {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        let len = socket.recv(&mut buffer).unwrap();
+        decode(&buffer[..len]).unwrap()
+    }
+
+    fn recv_welcome(socket: &UdpSocket) -> ServerMessage {
+        loop {
+            match recv(socket) {
+                ServerMessage::Snapshot(_) | ServerMessage::Lobby(_) => continue,
+                message => return message,
+            }
+        }
+    }
+
+    fn latest_snapshot(socket: &UdpSocket) -> Snapshot {
+        socket.set_nonblocking(true).unwrap();
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        let mut latest = None;
+        while let Ok(len) = socket.recv(&mut buffer) {
+            if let Ok(ServerMessage::Snapshot(snapshot)) = decode(&buffer[..len]) {
+                latest = Some(snapshot);
+            }
+        }
+        socket.set_nonblocking(false).unwrap();
+        latest.unwrap()
+    }
+
+    fn latest_lobby(socket: &UdpSocket) -> Lobby {
+        socket.set_nonblocking(true).unwrap();
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        let mut latest = None;
+        while let Ok(len) = socket.recv(&mut buffer) {
+            if let Ok(ServerMessage::Lobby(lobby)) = decode(&buffer[..len]) {
+                latest = Some(lobby);
+            }
+        }
+        socket.set_nonblocking(false).unwrap();
+        latest.unwrap()
+    }
+}
@@ -3,24 +3,26 @@ use ggez::event;
 use ggez::graphics::{self, Color, DrawMode, FillOptions, Rect};
 use ggez::nalgebra as na;
 
-use crate::state::MainState;
+use crate::objects::{Tank, TANK_DIMENSIONS};
+use crate::state::Player;
 
 const WIDTH: f32 = 10.67;
 const HEIGHT: f32 = 6.;
-const SPEED: f32 = 3.;
+/// Pixels per second.
+pub const SPEED: f32 = 180.;
+pub const DAMAGE: u32 = 25;
 
 #[derive(Clone)]
 pub struct Bullet {
     pub position: na::Point2<f32>,
     pub direction: na::Vector2<f32>,
     pub rotation: f32,
-    pub origin: na::Vector2<f32>,
+    pub owner: Player,
 }
 
 impl event::EventHandler for Bullet {
     fn update(&mut self, _: &mut ggez::Context) -> ggez::GameResult {
-        self.movement();
-        // send to server
+        // bullets are driven by `World::step` or the snapshots they arrive in
         Ok(())
     }
 
@@ -43,62 +45,84 @@ impl event::EventHandler for Bullet {
 }
 
 impl Bullet {
-    pub(crate) fn movement(&mut self) {
-        self.position += SPEED * self.direction;
+    /// A bullet leaving the tip of the tank's turret.
+    pub fn new(tank: &Tank) -> Self {
+        let (x, y) = tank.get_turret_end();
+        Bullet {
+            position: na::Point2::from([x, y]),
+            direction: tank.turret_direction,
+            rotation: tank.turret_rotation,
+            owner: tank.player,
+        }
     }
-}
 
-pub fn remove_bullet_if_outside_game_scren(
-    bullet_position: na::Point2<f32>,
-    main_state: &mut MainState,
-    coordinates: Rect,
-) {
-    if !coordinates.contains(bullet_position) {
-        main_state.bullet = None;
+    pub(crate) fn movement(&mut self, delta: f32) {
+        self.position += SPEED * delta * self.direction;
+    }
+
+    /// Damages the first tank the bullet is on, never the shooter's, and returns its player.
+    pub fn hit(&self, tanks: &mut [Tank]) -> Option<Player> {
+        let tank = tanks.iter_mut().find(|tank| {
+            tank.player != self.owner
+                && !tank.is_destroyed()
+                && tank.contains(self.position, TANK_DIMENSIONS)
+        })?;
+
+        tank.take_damage(DAMAGE);
+        Some(tank.player)
     }
 }
 
+pub fn remove_bullets_outside_game_screen(bullets: &mut Vec<Bullet>, coordinates: Rect) {
+    bullets.retain(|bullet| coordinates.contains(bullet.position));
+}
+
 #[cfg(test)]
 mod tests {
     use ggez::graphics::Rect;
     use ggez::nalgebra as na;
 
     use crate::objects::Tank;
-    use crate::state::MainState;
 
-    use super::{remove_bullet_if_outside_game_scren, Bullet};
+    use super::{remove_bullets_outside_game_screen, Bullet, DAMAGE};
+    use crate::objects::weapon::Weapon;
+    use crate::objects::MAX_HEALTH;
+    use crate::state::Player;
 
     #[test]
     fn bullet_is_outside_screen_x() {
-        let mut main_state = main();
-        let coord = coord();
-        let bullet_position = na::Point2::from([901., 1000.]);
+        let mut bullets = vec![Bullet {
+            position: na::Point2::from([901., 1000.]),
+            ..bullet()
+        }];
 
-        remove_bullet_if_outside_game_scren(bullet_position, &mut main_state, coord);
+        remove_bullets_outside_game_screen(&mut bullets, coord());
 
-        assert!(main_state.bullet.is_none())
+        assert!(bullets.is_empty())
     }
 
     #[test]
     fn bullet_is_outside_screen_y() {
-        let mut main_state = main();
-        let coord = coord();
-        let bullet_position = na::Point2::from([800., 1201.]);
+        let mut bullets = vec![Bullet {
+            position: na::Point2::from([800., 1201.]),
+            ..bullet()
+        }];
 
-        remove_bullet_if_outside_game_scren(bullet_position, &mut main_state, coord);
+        remove_bullets_outside_game_screen(&mut bullets, coord());
 
-        assert!(main_state.bullet.is_none())
+        assert!(bullets.is_empty())
     }
 
     #[test]
     fn bullet_is_inside_screen() {
-        let mut main_state = main();
-        let coord = coord();
-        let bullet_position = na::Point2::from([800., 1000.]);
+        let mut bullets = vec![Bullet {
+            position: na::Point2::from([800., 1000.]),
+            ..bullet()
+        }];
 
-        remove_bullet_if_outside_game_scren(bullet_position, &mut main_state, coord);
+        remove_bullets_outside_game_screen(&mut bullets, coord());
 
-        assert!(main_state.bullet.is_some())
+        assert_eq!(bullets.len(), 1)
     }
 
     #[test]
@@ -107,13 +131,68 @@ mod tests {
 
         assert_eq!(bullet.position, na::Point2::from([300., 400.]),);
 
-        bullet.movement();
-        assert_eq!(bullet.position, na::Point2::from([303., 403.]),);
+        bullet.movement(0.5);
+        assert_eq!(bullet.position, na::Point2::from([390., 490.]),);
+    }
+
+    #[test]
+    fn hits_damage_the_tank_under_the_bullet() {
+        let mut tanks = tanks();
+        let bullet = Bullet {
+            position: na::Point2::from([110., 100.]),
+            ..bullet()
+        };
+
+        assert_eq!(bullet.hit(&mut tanks), Some(Player::P2));
+        assert_eq!(tanks[1].health, MAX_HEALTH - DAMAGE);
+        assert_eq!(tanks[0].health, MAX_HEALTH);
     }
 
-    fn main() -> MainState {
-        MainState {
-            tank: Tank {
+    #[test]
+    fn shooter_is_never_hit() {
+        let mut tanks = tanks();
+        let bullet = Bullet {
+            position: na::Point2::from([300., 300.]),
+            ..bullet()
+        };
+
+        assert_eq!(bullet.hit(&mut tanks), None);
+        assert_eq!(tanks[0].health, MAX_HEALTH);
+    }
+
+    #[test]
+    fn hits_follow_the_tank_rotation() {
+        let mut tanks = tanks();
+        // rotated by 30 rad the enemy's body covers the point above its centre ...
+        let above = Bullet {
+            position: na::Point2::from([100., 40.]),
+            ..bullet()
+        };
+        // ... but not the one left of it
+        let left = Bullet {
+            position: na::Point2::from([40., 100.]),
+            ..bullet()
+        };
+
+        assert_eq!(above.hit(&mut tanks), Some(Player::P2));
+        assert_eq!(left.hit(&mut tanks), None);
+    }
+
+    #[test]
+    fn destroyed_tanks_are_not_hit() {
+        let mut tanks = tanks();
+        tanks[1].health = 0;
+        let bullet = Bullet {
+            position: na::Point2::from([100., 100.]),
+            ..bullet()
+        };
+
+        assert_eq!(bullet.hit(&mut tanks), None);
+    }
+
+    fn tanks() -> Vec<Tank> {
+        vec![
+            Tank {
                 position: na::Point2::from([300., 300.]),
                 tank_direction: na::Vector2::from([1., 1.]),
                 tank_rotation: 30.,
@@ -121,13 +200,13 @@ mod tests {
                 turret_texture: None,
                 turret_direction: na::Vector2::from([1., 1.]),
                 turret_rotation: 30.,
-                turret_rotation_origin: na::Vector2::from([1., 1.]),
                 player: crate::state::Player::P1,
                 turret_width: 0.,
+                health: MAX_HEALTH,
+                armor: 0,
+                weapon: Weapon::default(),
             },
-            bullet: Some(bullet()),
-            coordinate: coord(),
-            enemy: Tank {
+            Tank {
                 position: na::Point2::from([100., 100.]),
                 tank_direction: na::Vector2::from([1., 1.]),
                 tank_rotation: 30.,
@@ -135,11 +214,13 @@ mod tests {
                 turret_texture: None,
                 turret_direction: na::Vector2::from([1., 1.]),
                 turret_rotation: 30.,
-                turret_rotation_origin: na::Vector2::from([1., 1.]),
                 player: crate::state::Player::P2,
                 turret_width: 0.,
+                health: MAX_HEALTH,
+                armor: 0,
+                weapon: Weapon::default(),
             },
-        }
+        ]
     }
 
     fn coord() -> Rect {
@@ -156,7 +237,7 @@ mod tests {
             position: na::Point2::from([300., 400.]),
             direction: na::Vector2::from([1., 1.]),
             rotation: 30.,
-            origin: na::Vector2::from([1., 1.]),
+            owner: Player::P1,
         }
     }
 }
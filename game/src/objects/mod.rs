@@ -1,17 +1,25 @@
-use std::collections::HashSet;
-
 use ggez;
 use ggez::event;
-use ggez::event::KeyCode;
-use ggez::graphics::{self, screen_coordinates, Rect};
-use ggez::input::mouse;
+use ggez::graphics::{self, Rect};
 use ggez::nalgebra as na;
-use ggez::timer::delta;
+use ggez::{Context, GameResult};
 
+use crate::collision::Obb;
+use crate::network::protocol::{PlayerInput, TankSnapshot};
+use crate::objects::bullet::Bullet;
+use crate::objects::weapon::Weapon;
 use crate::state::Player;
 
 pub mod bullet;
+pub mod weapon;
+
+/// Size of `TankBase.png`, used when a tank has no texture loaded (e.g. on the server).
+pub const TANK_DIMENSIONS: Rect = Rect::new(0., 0., 146., 100.);
+pub const MAX_HEALTH: u32 = 100;
+/// Pixels per second, forwards or backwards.
+pub const TANK_SPEED: f32 = 60.;
 
+#[derive(Clone)]
 pub struct Tank {
     pub position: na::Point2<f32>,
     pub tank_direction: na::Vector2<f32>,
@@ -20,27 +28,17 @@ pub struct Tank {
     pub turret_texture: Option<graphics::Image>,
     pub turret_direction: na::Vector2<f32>,
     pub turret_rotation: f32,
-    pub turret_rotation_origin: na::Vector2<f32>,
     pub player: Player,
     pub turret_width: f32,
+    pub health: u32,
+    /// Subtracted from the damage of every hit.
+    pub armor: u32,
+    pub weapon: Weapon,
 }
 
 impl event::EventHandler for Tank {
-    fn update(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult {
-        if self.player == Player::P1 {
-            let keys = ggez::input::keyboard::pressed_keys(ctx);
-            let delta = delta(ctx).as_secs_f32();
-            let mouse_position = mouse::position(ctx);
-            let mouse_position = na::Point2::from([mouse_position.x + 75., mouse_position.y]);
-            let screen_coord = screen_coordinates(ctx);
-            let dim = self.texture.as_ref().unwrap().dimensions();
-            self.movement(keys, screen_coord.clone(), dim, &Rect::new(0., 0., 0., 0.));
-            self.rotation(keys, delta);
-            self.update_turret_direction(mouse_position.into());
-            //send to server
-        } else {
-            // update from server
-        }
+    fn update(&mut self, _: &mut ggez::Context) -> ggez::GameResult {
+        // tanks are driven by `World::step` or `Tank::apply_snapshot`
         Ok(())
     }
 
@@ -62,59 +60,76 @@ impl event::EventHandler for Tank {
 }
 
 impl Tank {
-    pub fn update_(&mut self, ctx: &mut ggez::Context, enemy: &Tank) -> ggez::GameResult {
-        if self.player == Player::P1 {
-            let keys = ggez::input::keyboard::pressed_keys(ctx);
-            let delta = delta(ctx).as_secs_f32();
-            let mouse_position = mouse::position(ctx);
-            let mouse_position = na::Point2::from([mouse_position.x + 75., mouse_position.y]);
-            let screen_coord = screen_coordinates(ctx);
-            let enemy_texture = enemy.texture.as_ref().unwrap().dimensions();
-            let mut enemy_rect = Rect::new(
-                enemy.position.x,
-                enemy.position.y,
-                enemy_texture.w,
-                enemy_texture.h,
-            );
-            enemy_rect.rotate(enemy.tank_rotation);
-            let dim = self.texture.as_ref().unwrap().dimensions();
-
-            self.movement(keys, screen_coord, dim, &enemy_rect);
-            self.rotation(keys, delta);
-            self.update_turret_direction(mouse_position.into());
-            //send to server
-        } else {
-            // update from server
+    pub fn new(ctx: &mut Context, player: Player, position: na::Point2<f32>) -> GameResult<Tank> {
+        let tank_base = graphics::Image::new(ctx, "/TankBase.png")?;
+        let tank_dimensions = tank_base.dimensions();
+
+        Ok(Tank {
+            texture: Some(tank_base),
+            turret_texture: Some(graphics::Image::new(ctx, "/TankTops.png")?),
+            turret_width: tank_dimensions.w * 0.4,
+            ..Tank::headless(player, position)
+        })
+    }
+
+    /// A tank without textures, for simulations that never draw it.
+    pub fn headless(player: Player, position: na::Point2<f32>) -> Tank {
+        Tank {
+            position,
+            tank_direction: na::Vector2::from([-1., 0.]),
+            tank_rotation: 0.,
+            texture: None,
+            turret_texture: None,
+            turret_direction: na::Vector2::from([-1., 0.]),
+            turret_rotation: 0.,
+            player,
+            turret_width: TANK_DIMENSIONS.w * 0.4,
+            health: MAX_HEALTH,
+            armor: 0,
+            weapon: Weapon::default(),
         }
-        Ok(())
     }
 
     pub fn movement(
         &mut self,
-        keys: &HashSet<KeyCode>,
+        input: &PlayerInput,
         screen_coord: Rect,
         tank_dim: Rect,
-        enenmy: &Rect,
+        others: &[Obb],
+        delta: f32,
     ) {
-        let mut rect = Rect::new(self.position.x, self.position.y, tank_dim.w, tank_dim.h);
-        rect.rotate(self.tank_rotation);
+        let distance = TANK_SPEED * delta;
 
-        if keys.contains(&KeyCode::W) || keys.contains(&KeyCode::Up) {
-            self.update_position(1., screen_coord, &mut rect, enenmy);
+        if input.forward {
+            self.update_position(distance, screen_coord, tank_dim, others);
         }
 
-        if keys.contains(&KeyCode::S) || keys.contains(&KeyCode::Down) {
-            self.update_position(-1., screen_coord, &mut rect, enenmy);
+        if input.backward {
+            self.update_position(-distance, screen_coord, tank_dim, others);
         }
     }
 
-    pub fn rotation(&mut self, keys: &HashSet<KeyCode>, delta: f32) {
-        if keys.contains(&KeyCode::D) || keys.contains(&KeyCode::Right) {
+    /// Applies one networked input, the same way on the server and on the predicting client.
+    pub fn apply_input(
+        &mut self,
+        input: &PlayerInput,
+        screen_coord: Rect,
+        tank_dim: Rect,
+        others: &[Obb],
+        delta: f32,
+    ) {
+        self.movement(input, screen_coord, tank_dim, others, delta);
+        self.rotation(input, delta);
+        self.update_turret_direction(input.aim());
+    }
+
+    pub fn rotation(&mut self, input: &PlayerInput, delta: f32) {
+        if input.turn_right {
             self.tank_rotation += delta;
             self.update_direction();
         }
 
-        if keys.contains(&KeyCode::A) || keys.contains(&KeyCode::Left) {
+        if input.turn_left {
             self.tank_rotation -= delta;
             self.update_direction();
         }
@@ -129,35 +144,78 @@ impl Tank {
         &mut self,
         direction: f32,
         screen_coord: Rect,
-        rect: &mut Rect,
-        enemy: &Rect,
+        tank_dim: Rect,
+        others: &[Obb],
     ) {
         let new_position = na::Point2::from([
             self.position.x + (direction * self.tank_direction.x),
             self.position.y + (direction * self.tank_direction.y),
         ]);
+        let hitbox = Obb::new(new_position, tank_dim.w, tank_dim.h, self.tank_rotation);
 
-        rect.x = new_position.x;
-        rect.y = new_position.y;
-
-        if screen_coord.contains(new_position) && !rect.overlaps(enemy) {
+        if screen_coord.contains(new_position) && !others.iter().any(|other| hitbox.overlaps(other))
+        {
             self.position = new_position;
         }
     }
 
+    /// Where the turret is drawn rotating around, and so where it aims from.
+    pub fn turret_origin(&self) -> na::Point2<f32> {
+        self.position
+    }
+
     pub fn update_turret_direction(&mut self, mouse_position: na::Point2<f32>) {
         let mouse = na::Vector2::from([mouse_position.x, mouse_position.y]);
-        let origin = na::Vector2::from([
-            self.position.x + self.turret_rotation_origin.x,
-            self.position.y + self.turret_rotation_origin.y,
-        ]);
-        let direction: na::Vector2<f32> = (mouse - origin).into();
+        let origin = self.turret_origin().coords;
+        let direction: na::Vector2<f32> = mouse - origin;
         let angle = (mouse.y - origin.y).atan2(origin.x - mouse.x);
 
         self.turret_direction = direction.normalize();
         self.turret_rotation = -angle;
     }
 
+    pub fn apply_snapshot(&mut self, snapshot: &TankSnapshot) {
+        let (sin, cos) = snapshot.turret_rotation.sin_cos();
+
+        self.player = snapshot.player;
+        self.position = na::Point2::from(snapshot.position);
+        self.tank_rotation = snapshot.tank_rotation;
+        self.update_direction();
+        self.health = snapshot.health;
+        self.turret_rotation = snapshot.turret_rotation;
+        self.turret_direction = na::Vector2::from([-cos, -sin]);
+    }
+
+    /// Whether `point` is on the tank as drawn: centred on `position` and rotated by `tank_rotation`.
+    pub fn contains(&self, point: na::Point2<f32>, tank_dim: Rect) -> bool {
+        self.hitbox(tank_dim).contains(point)
+    }
+
+    /// Applies `damage` minus armor, but at least 1. Returns `true` if it destroyed the tank.
+    pub fn take_damage(&mut self, damage: u32) -> bool {
+        let damage = damage.saturating_sub(self.armor).max(1);
+        self.health = self.health.saturating_sub(damage);
+        self.is_destroyed()
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.health == 0
+    }
+
+    /// A bullet from the turret's end, if the weapon is ready to shoot it.
+    pub fn fire(&mut self) -> Option<Bullet> {
+        if self.weapon.fire() {
+            Some(Bullet::new(self))
+        } else {
+            None
+        }
+    }
+
+    /// The tank's body as drawn, used to block other tanks' movement and for bullet hits.
+    pub fn hitbox(&self, tank_dim: Rect) -> Obb {
+        Obb::new(self.position, tank_dim.w, tank_dim.h, self.tank_rotation)
+    }
+
     fn get_player_turret(&self) -> Rect {
         let draw_height = 0.5;
         let draw_width = 0.5;
@@ -200,9 +258,25 @@ impl Tank {
     }
 }
 
+/// Hitboxes of every tank but `player`'s, the obstacles its movement is checked against.
+pub fn tank_hitboxes<'a>(player: Player, tanks: impl Iterator<Item = &'a Tank>) -> Vec<Obb> {
+    tanks
+        .filter(|tank| tank.player != player)
+        .map(|tank| tank.hitbox(TANK_DIMENSIONS))
+        .collect()
+}
+
+/// Lets every bullet in flight damage what it hits, using the bullet up, and
+/// removes the tanks that were destroyed.
+pub fn resolve_hits(tanks: &mut Vec<Tank>, bullets: &mut Vec<Bullet>) {
+    bullets.retain(|bullet| bullet.hit(tanks).is_none());
+    tanks.retain(|tank| !tank.is_destroyed());
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::network::INPUT_DELTA;
 
     #[test]
     fn initial_info() {
@@ -216,11 +290,13 @@ mod test {
     fn move_forward() {
         let mut tank = tank();
         let tank_dim = Rect::new(0., 0., 10., 10.);
-        let keys = vec![KeyCode::W].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &Rect::new(0., 0., 0., 0.));
+        let input = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord(), tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([399., 300.]));
-        let keys = vec![KeyCode::Up].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &Rect::new(0., 0., 0., 0.));
+        tank.movement(&input, screen_coord(), tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([398., 300.]));
     }
 
@@ -228,11 +304,13 @@ mod test {
     fn move_backwards() {
         let mut tank = tank();
         let tank_dim = Rect::new(0., 0., 10., 10.);
-        let keys = vec![KeyCode::S].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &Rect::new(0., 0., 0., 0.));
+        let input = PlayerInput {
+            backward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord(), tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([401., 300.]));
-        let keys = vec![KeyCode::Down].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &Rect::new(0., 0., 0., 0.));
+        tank.movement(&input, screen_coord(), tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([402., 300.]));
     }
 
@@ -240,16 +318,28 @@ mod test {
     fn turn_left() {
         let mut tank = tank();
         let tank_dim = Rect::new(0., 0., 10., 10.);
-        let keys = vec![KeyCode::A].into_iter().collect();
-        tank.rotation(&keys, 0.3);
-        let keys = vec![KeyCode::W].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &Rect::new(0., 0., 0., 0.));
+        let input = PlayerInput {
+            turn_left: true,
+            ..PlayerInput::default()
+        };
+        tank.rotation(&input, 0.3);
+        let input = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord(), tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([399.04468, 300.29553]));
 
-        let keys = vec![KeyCode::Left].into_iter().collect();
-        tank.rotation(&keys, 0.5);
-        let keys = vec![KeyCode::W].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &Rect::new(0., 0., 0., 0.));
+        let input = PlayerInput {
+            turn_left: true,
+            ..PlayerInput::default()
+        };
+        tank.rotation(&input, 0.5);
+        let input = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord(), tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([398.34796, 301.01288]));
     }
 
@@ -257,16 +347,28 @@ mod test {
     fn turn_right() {
         let mut tank = tank();
         let tank_dim = Rect::new(0., 0., 10., 10.);
-        let keys = vec![KeyCode::D].into_iter().collect();
-        tank.rotation(&keys, 0.3);
-        let keys = vec![KeyCode::S].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &Rect::new(0., 0., 0., 0.));
+        let input = PlayerInput {
+            turn_right: true,
+            ..PlayerInput::default()
+        };
+        tank.rotation(&input, 0.3);
+        let input = PlayerInput {
+            backward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord(), tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([400.95532, 300.29553]));
 
-        let keys = vec![KeyCode::Right].into_iter().collect();
-        tank.rotation(&keys, 0.5);
-        let keys = vec![KeyCode::S].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &Rect::new(0., 0., 0., 0.));
+        let input = PlayerInput {
+            turn_right: true,
+            ..PlayerInput::default()
+        };
+        tank.rotation(&input, 0.5);
+        let input = PlayerInput {
+            backward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord(), tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([401.65204, 301.01288]));
     }
 
@@ -317,8 +419,11 @@ mod test {
         };
         let mut tank = tank();
         let tank_dim = Rect::new(0., 0., 10., 10.);
-        let keys = vec![KeyCode::S].into_iter().collect();
-        tank.movement(&keys, screen_coord, tank_dim, &Rect::new(0., 0., 0., 0.));
+        let input = PlayerInput {
+            backward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord, tank_dim, &[], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([400., 300.]));
     }
 
@@ -326,12 +431,248 @@ mod test {
     fn collide_with_enemy() {
         let mut tank = tank();
         let tank_dim = Rect::new(0., 0., 10., 10.);
-        let enemy = Rect::new(401., 301., 10., 10.);
-        let keys = vec![KeyCode::W].into_iter().collect();
-        tank.movement(&keys, screen_coord(), tank_dim, &enemy);
+        let enemy = Obb::new(na::Point2::from([390., 300.]), 10., 10., 0.);
+        let input = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord(), tank_dim, &[enemy], INPUT_DELTA);
         assert_eq!(tank.position, na::Point2::from([400., 300.]));
     }
 
+    #[test]
+    fn collide_with_any_other_tank() {
+        let mut tank = tank();
+        let tank_dim = Rect::new(0., 0., 10., 10.);
+        let others = [
+            Obb::new(na::Point2::from([100., 100.]), 10., 10., 0.),
+            Obb::new(na::Point2::from([390., 305.]), 10., 10., 0.),
+        ];
+        let input = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+        tank.movement(&input, screen_coord(), tank_dim, &others, INPUT_DELTA);
+        assert_eq!(tank.position, na::Point2::from([400., 300.]));
+    }
+
+    #[test]
+    fn parks_next_to_a_tank_without_touching_it() {
+        let mut tank = tank();
+        let tank_dim = Rect::new(0., 0., 10., 10.);
+        let neighbour = Obb::new(na::Point2::from([400., 310.]), 10., 10., 0.);
+        let input = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+
+        tank.movement(&input, screen_coord(), tank_dim, &[neighbour], INPUT_DELTA);
+
+        assert_eq!(tank.position, na::Point2::from([399., 300.]));
+    }
+
+    #[test]
+    fn angled_tanks_collide_only_when_their_bodies_do() {
+        use std::f32::consts::FRAC_PI_4;
+
+        let input = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+        // both at 45 degrees, side by side along the diagonal: the unrotated
+        // bounding rects would overlap, the bodies don't
+        let mut tank = Tank {
+            tank_rotation: FRAC_PI_4,
+            ..tank()
+        };
+        tank.update_direction();
+        let beside = Tank {
+            position: na::Point2::from([325., 375.]),
+            ..tank.clone()
+        };
+
+        tank.movement(
+            &input,
+            screen_coord(),
+            TANK_DIMENSIONS,
+            &[beside.hitbox(TANK_DIMENSIONS)],
+            INPUT_DELTA,
+        );
+        assert_ne!(tank.position, na::Point2::from([400., 300.]));
+
+        // half a pixel in front of the tank's nose, one step forward closes the gap
+        let before = tank.position;
+        let ahead = Tank {
+            position: before + tank.tank_direction * (TANK_DIMENSIONS.w + 0.5),
+            ..tank.clone()
+        };
+        tank.movement(
+            &input,
+            screen_coord(),
+            TANK_DIMENSIONS,
+            &[ahead.hitbox(TANK_DIMENSIONS)],
+            INPUT_DELTA,
+        );
+        assert_eq!(tank.position, before);
+    }
+
+    #[test]
+    fn headless_tank_matches_texture_layout() {
+        let tank = Tank::headless(Player::P2, na::Point2::from([10., 20.]));
+
+        assert!(tank.texture.is_none());
+        assert_eq!(tank.player, Player::P2);
+        assert_eq!(tank.position, na::Point2::from([10., 20.]));
+        assert_eq!(tank.turret_width, 58.4);
+    }
+
+    #[test]
+    fn apply_snapshot() {
+        use float_eq::float_eq;
+
+        let mut tank = tank();
+        tank.apply_snapshot(&TankSnapshot {
+            player: Player::P3,
+            position: [10., 20.],
+            tank_rotation: std::f32::consts::FRAC_PI_2,
+            turret_rotation: std::f32::consts::PI,
+            health: 40,
+            last_input: 0,
+        });
+
+        assert_eq!(tank.player, Player::P3);
+        assert_eq!(tank.position, na::Point2::from([10., 20.]));
+        assert_eq!(tank.health, 40);
+        assert!(float_eq!(tank.tank_direction.x, 0., abs <= 0.000_1));
+        assert!(float_eq!(tank.tank_direction.y, -1., abs <= 0.000_1));
+        assert!(float_eq!(tank.turret_direction.x, 1., abs <= 0.000_1));
+        assert!(float_eq!(tank.turret_direction.y, 0., abs <= 0.000_1));
+    }
+
+    #[test]
+    fn apply_input() {
+        let mut tank = tank();
+        let input = PlayerInput {
+            forward: true,
+            turn_right: true,
+            aim: [370., 400.],
+            ..PlayerInput::default()
+        };
+
+        tank.apply_input(&input, screen_coord(), TANK_DIMENSIONS, &[], 0.5);
+
+        assert_eq!(tank.position, na::Point2::from([370., 300.]));
+        assert_eq!(tank.tank_rotation, 0.5);
+        assert_eq!(tank.turret_direction, na::Vector2::from([0., 1.]));
+    }
+
+    #[test]
+    fn tank_hitboxes_skips_the_players_own_tank() {
+        let tanks = [
+            Tank::headless(Player::P1, na::Point2::from([400., 300.])),
+            Tank::headless(Player::P2, na::Point2::from([500., 300.])),
+            Tank::headless(Player::P3, na::Point2::from([900., 300.])),
+        ];
+
+        let hitboxes = tank_hitboxes(Player::P1, tanks.iter());
+
+        assert_eq!(
+            hitboxes,
+            vec![
+                Obb::new(na::Point2::from([500., 300.]), 146., 100., 0.),
+                Obb::new(na::Point2::from([900., 300.]), 146., 100., 0.)
+            ]
+        );
+    }
+
+    #[test]
+    fn contains_points_of_the_drawn_tank() {
+        let tank = tank();
+
+        assert!(tank.contains(na::Point2::from([470., 345.]), TANK_DIMENSIONS));
+        assert!(tank.contains(na::Point2::from([330., 255.]), TANK_DIMENSIONS));
+        assert!(!tank.contains(na::Point2::from([400., 355.]), TANK_DIMENSIONS));
+        assert!(!tank.contains(na::Point2::from([480., 300.]), TANK_DIMENSIONS));
+    }
+
+    #[test]
+    fn contains_follows_the_tank_rotation() {
+        let mut tank = tank();
+        tank.tank_rotation = std::f32::consts::FRAC_PI_2;
+
+        assert!(tank.contains(na::Point2::from([400., 370.]), TANK_DIMENSIONS));
+        assert!(tank.contains(na::Point2::from([445., 230.]), TANK_DIMENSIONS));
+        assert!(!tank.contains(na::Point2::from([470., 300.]), TANK_DIMENSIONS));
+    }
+
+    #[test]
+    fn contains_at_an_angle() {
+        let mut tank = tank();
+        tank.tank_rotation = std::f32::consts::FRAC_PI_4;
+
+        // the corner of the unrotated tank now sticks out of the body
+        assert!(!tank.contains(na::Point2::from([470., 345.]), TANK_DIMENSIONS));
+        // 70 along the rotated length axis
+        assert!(tank.contains(na::Point2::from([449.5, 349.5]), TANK_DIMENSIONS));
+    }
+
+    #[test]
+    fn armor_reduces_damage_down_to_one() {
+        let mut tank = Tank {
+            armor: 10,
+            ..tank()
+        };
+
+        assert!(!tank.take_damage(25));
+        assert_eq!(tank.health, 85);
+        assert!(!tank.take_damage(5));
+        assert_eq!(tank.health, 84);
+    }
+
+    #[test]
+    fn enough_damage_destroys_the_tank() {
+        let mut tank = tank();
+
+        assert!(!tank.take_damage(60));
+        assert!(tank.take_damage(60));
+        assert_eq!(tank.health, 0);
+        assert!(tank.is_destroyed());
+    }
+
+    #[test]
+    fn fire_uses_the_weapon() {
+        let mut tank = tank();
+
+        assert_eq!(tank.fire().unwrap().owner, Player::P1);
+        assert!(tank.fire().is_none());
+        assert_eq!(tank.weapon.ammo, tank.weapon.config.magazine_size - 1);
+    }
+
+    #[test]
+    fn resolve_hits_uses_up_bullets_and_removes_destroyed_tanks() {
+        let mut shooter = tank();
+        let mut target = Tank::headless(Player::P2, na::Point2::from([100., 100.]));
+        target.health = 1;
+        let bullet = shooter.fire().unwrap();
+        let mut bullets = vec![
+            Bullet {
+                position: target.position,
+                ..bullet.clone()
+            },
+            Bullet {
+                position: na::Point2::from([900., 800.]),
+                ..bullet
+            },
+        ];
+        let mut tanks = vec![shooter, target];
+
+        resolve_hits(&mut tanks, &mut bullets);
+
+        assert_eq!(tanks.len(), 1);
+        assert_eq!(bullets.len(), 1);
+        assert_eq!(bullets[0].position, na::Point2::from([900., 800.]));
+    }
+
     fn tank() -> Tank {
         Tank {
             position: na::Point2::from([400., 300.]),
@@ -340,10 +681,12 @@ mod test {
             texture: None,
             turret_texture: None,
             turret_direction: na::Vector2::from([-1., 0.]),
-            turret_rotation_origin: na::Vector2::from([0., 0.]),
             turret_rotation: 0.,
             player: Player::P1,
             turret_width: 5.,
+            health: MAX_HEALTH,
+            armor: 0,
+            weapon: Weapon::default(),
         }
     }
 
@@ -0,0 +1,134 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeaponConfig {
+    /// Shots per second.
+    pub fire_rate: f32,
+    pub magazine_size: u32,
+    /// Seconds to refill an empty magazine.
+    pub reload_time: f32,
+}
+
+impl Default for WeaponConfig {
+    fn default() -> Self {
+        WeaponConfig {
+            fire_rate: 4.,
+            magazine_size: 6,
+            reload_time: 1.5,
+        }
+    }
+}
+
+/// A tank's gun. Its bullets belong to the `World`, so they keep flying
+/// after the tank is gone.
+#[derive(Clone)]
+pub struct Weapon {
+    pub config: WeaponConfig,
+    pub ammo: u32,
+    /// Seconds until the next shot is allowed.
+    pub cooldown: f32,
+    /// Seconds until the magazine is full again, 0 when not reloading.
+    pub reload: f32,
+}
+
+impl Default for Weapon {
+    fn default() -> Self {
+        Weapon::new(WeaponConfig::default())
+    }
+}
+
+impl Weapon {
+    pub fn new(config: WeaponConfig) -> Self {
+        Weapon {
+            config,
+            ammo: config.magazine_size,
+            cooldown: 0.,
+            reload: 0.,
+        }
+    }
+
+    /// Advances the fire-rate cooldown and any reload by `delta` seconds.
+    pub fn cool_down(&mut self, delta: f32) {
+        self.cooldown = (self.cooldown - delta).max(0.);
+        if self.is_reloading() {
+            self.reload -= delta;
+            if self.reload <= 0. {
+                self.reload = 0.;
+                self.ammo = self.config.magazine_size;
+            }
+        }
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload > 0.
+    }
+
+    pub fn can_fire(&self) -> bool {
+        self.ammo > 0 && self.cooldown <= 0. && !self.is_reloading()
+    }
+
+    /// Uses up a round if the weapon is ready, returning whether it did.
+    /// Firing the last round starts reloading.
+    pub fn fire(&mut self) -> bool {
+        if !self.can_fire() {
+            return false;
+        }
+
+        self.ammo -= 1;
+        self.cooldown = 1. / self.config.fire_rate;
+        if self.ammo == 0 {
+            self.reload = self.config.reload_time;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_magazine_to_start_with() {
+        let weapon = weapon();
+
+        assert_eq!(weapon.ammo, 3);
+        assert!(weapon.can_fire());
+    }
+
+    #[test]
+    fn fire_rate_spaces_out_shots() {
+        let mut weapon = weapon();
+
+        assert!(weapon.fire());
+        assert!(!weapon.fire());
+        weapon.cool_down(0.4);
+        assert!(!weapon.fire());
+        weapon.cool_down(0.2);
+        assert!(weapon.fire());
+
+        assert_eq!(weapon.ammo, 1);
+    }
+
+    #[test]
+    fn empty_magazine_reloads() {
+        let mut weapon = weapon();
+        for _ in 0..3 {
+            assert!(weapon.fire());
+            weapon.cool_down(0.5);
+        }
+
+        assert_eq!(weapon.ammo, 0);
+        assert!(weapon.is_reloading());
+        assert!(!weapon.fire());
+
+        weapon.cool_down(1.);
+        assert!(weapon.can_fire());
+        assert_eq!(weapon.ammo, 3);
+    }
+
+    fn weapon() -> Weapon {
+        Weapon::new(WeaponConfig {
+            fire_rate: 2.,
+            magazine_size: 3,
+            reload_time: 1.,
+        })
+    }
+}
@@ -0,0 +1,308 @@
+use ggez::graphics::Rect;
+
+use crate::map::{Map, TileMap};
+use crate::network::protocol::{BulletSnapshot, PlayerInput, Snapshot, TankSnapshot};
+use crate::objects::bullet::{remove_bullets_outside_game_screen, Bullet};
+use crate::objects::{resolve_hits, tank_hitboxes, Tank, TANK_DIMENSIONS};
+use crate::state::{spawn_positions, Player};
+
+/// The state of a match without textures, a window or a `Context`: tanks, the
+/// bullets in flight and the arena. Inputs go in, the next state comes out, so the same
+/// rules run in the local game, on the server, for bots and in tests.
+#[derive(Clone)]
+pub struct World {
+    pub tanks: Vec<Tank>,
+    /// Every tank's, including those of tanks destroyed since they fired.
+    pub bullets: Vec<Bullet>,
+    pub coordinate: Rect,
+    pub map: TileMap,
+}
+
+impl World {
+    /// A headless tank for every player, spread around an open arena.
+    pub fn new(players: &[Player], coordinate: Rect) -> World {
+        World::with_map(players, coordinate, Map::default())
+    }
+
+    /// A headless tank for every player at their spawn on `map`, which fills
+    /// `coordinate`. Players the map has no spawn for are spread around it.
+    pub fn with_map(players: &[Player], coordinate: Rect, map: Map) -> World {
+        World {
+            tanks: players
+                .iter()
+                .zip(spawn_positions(players.len(), coordinate))
+                .map(|(player, position)| {
+                    Tank::headless(*player, map.spawn(*player).unwrap_or(position))
+                })
+                .collect(),
+            bullets: Vec::new(),
+            coordinate,
+            map: map.tiles,
+        }
+    }
+
+    pub fn tank(&self, player: Player) -> Option<&Tank> {
+        self.tanks.iter().find(|tank| tank.player == player)
+    }
+
+    /// Drives `player`'s tank with one input covering `delta` seconds. Returns
+    /// `false`, doing nothing, if the tank was destroyed.
+    pub fn apply_input(&mut self, player: Player, input: &PlayerInput, delta: f32) -> bool {
+        let index = match self.tanks.iter().position(|tank| tank.player == player) {
+            Some(index) => index,
+            None => return false,
+        };
+        let mut others = tank_hitboxes(player, self.tanks.iter());
+        others.extend(self.map.tank_obstacles());
+        let tank = &mut self.tanks[index];
+
+        tank.apply_input(input, self.coordinate, TANK_DIMENSIONS, &others, delta);
+        if input.fire {
+            self.bullets.extend(tank.fire());
+        }
+        true
+    }
+
+    /// Everything that happens without input: weapons cool down and reload,
+    /// bullets fly, hit walls, tanks and leave the arena, destroyed tanks are
+    /// removed.
+    pub fn advance(&mut self, delta: f32) {
+        for tank in &mut self.tanks {
+            tank.weapon.cool_down(delta);
+        }
+        for bullet in &mut self.bullets {
+            bullet.movement(delta);
+        }
+        remove_bullets_outside_game_screen(&mut self.bullets, self.coordinate);
+        let map = &self.map;
+        self.bullets
+            .retain(|bullet| !map.blocks_bullet(bullet.position));
+        resolve_hits(&mut self.tanks, &mut self.bullets);
+    }
+
+    /// One simulation step: every player's input, then `advance`.
+    pub fn step(&mut self, inputs: &[(Player, PlayerInput)], delta: f32) {
+        for (player, input) in inputs {
+            self.apply_input(*player, input, delta);
+        }
+        self.advance(delta);
+    }
+
+    /// Simulation state of every tank and bullet.
+    pub fn snapshot(&self, tick: u64) -> Snapshot {
+        Snapshot {
+            tick,
+            tanks: self.tanks.iter().map(TankSnapshot::from_tank).collect(),
+            bullets: self
+                .bullets
+                .iter()
+                .map(BulletSnapshot::from_bullet)
+                .collect(),
+        }
+    }
+
+    pub fn apply_snapshot(&mut self, snapshot: &Snapshot) {
+        for tank in &mut self.tanks {
+            if let Some(tank_snapshot) = snapshot
+                .tanks
+                .iter()
+                .find(|tank_snapshot| tank_snapshot.player == tank.player)
+            {
+                tank.apply_snapshot(tank_snapshot);
+            }
+        }
+        self.bullets = snapshot
+            .bullets
+            .iter()
+            .map(BulletSnapshot::to_bullet)
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ggez::nalgebra as na;
+
+    use super::*;
+    use crate::network::INPUT_DELTA;
+    use crate::objects::bullet::DAMAGE;
+    use crate::objects::MAX_HEALTH;
+
+    #[test]
+    fn tanks_spawn_without_textures() {
+        let world = world();
+
+        assert_eq!(world.tanks.len(), 2);
+        assert!(world.tanks.iter().all(|tank| tank.texture.is_none()));
+        assert_eq!(
+            world.tank(Player::P2).unwrap().position,
+            na::Point2::from([150., 450.])
+        );
+    }
+
+    #[test]
+    fn inputs_drive_only_their_players_tank() {
+        let mut world = world();
+        let forward = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+
+        world.step(&[(Player::P1, forward)], INPUT_DELTA);
+
+        assert_eq!(
+            world.tank(Player::P1).unwrap().position,
+            na::Point2::from([1049., 450.])
+        );
+        assert_eq!(
+            world.tank(Player::P2).unwrap().position,
+            na::Point2::from([150., 450.])
+        );
+    }
+
+    #[test]
+    fn fired_bullets_fly_and_hit() {
+        let mut world = world();
+        let fire = PlayerInput {
+            fire: true,
+            aim: [150., 450.],
+            ..PlayerInput::default()
+        };
+
+        world.step(&[(Player::P1, fire)], INPUT_DELTA);
+        assert_eq!(world.bullets.len(), 1);
+        for _ in 0..(5 * crate::network::TICK_RATE) {
+            world.step(&[], INPUT_DELTA);
+        }
+
+        assert!(world.bullets.is_empty());
+        assert_eq!(world.tank(Player::P2).unwrap().health, MAX_HEALTH - DAMAGE);
+    }
+
+    #[test]
+    fn bullets_outlive_the_tank_that_fired_them() {
+        let mut world = world();
+        let fire = PlayerInput {
+            fire: true,
+            aim: [150., 450.],
+            ..PlayerInput::default()
+        };
+        world.step(&[(Player::P1, fire)], INPUT_DELTA);
+        world.tanks[0].health = DAMAGE;
+        world.bullets.push(Bullet {
+            position: world.tanks[0].position,
+            ..Bullet::new(&world.tanks[1])
+        });
+
+        world.advance(0.);
+
+        assert_eq!(world.tanks.len(), 1);
+        assert_eq!(world.tanks[0].player, Player::P2);
+        assert_eq!(world.bullets.len(), 1);
+        assert_eq!(world.bullets[0].owner, Player::P1);
+        for _ in 0..(5 * crate::network::TICK_RATE) {
+            world.step(&[], INPUT_DELTA);
+        }
+        assert_eq!(world.tank(Player::P2).unwrap().health, MAX_HEALTH - DAMAGE);
+    }
+
+    #[test]
+    fn destroyed_tanks_ignore_input() {
+        let mut world = world();
+        world.tanks.retain(|tank| tank.player != Player::P2);
+
+        assert!(!world.apply_input(Player::P2, &PlayerInput::default(), INPUT_DELTA));
+    }
+
+    #[test]
+    fn snapshots_restore_tanks_and_bullets() {
+        let mut world = world();
+        world.apply_input(
+            Player::P2,
+            &PlayerInput {
+                fire: true,
+                turn_left: true,
+                ..PlayerInput::default()
+            },
+            0.5,
+        );
+
+        let mut restored = self::world();
+        restored.apply_snapshot(&world.snapshot(3));
+
+        assert_eq!(restored.snapshot(3), world.snapshot(3));
+    }
+
+    #[test]
+    fn walls_and_water_stop_tanks() {
+        let forward = PlayerInput {
+            forward: true,
+            ..PlayerInput::default()
+        };
+
+        for map in &["...#", "...~"] {
+            let mut world = world();
+            world.map = map.parse().unwrap();
+            world.tanks[1].position = na::Point2::from([300., 25.]);
+
+            // P2 faces left, its front is 27 pixels from the tile ending at 200
+            for _ in 0..100 {
+                world.apply_input(Player::P2, &forward, INPUT_DELTA);
+            }
+
+            assert_eq!(world.tank(Player::P2).unwrap().position.x, 273., "{}", map);
+        }
+    }
+
+    #[test]
+    fn walls_and_cover_stop_bullets_water_does_not() {
+        let mut world = world();
+        world.map = ".#~%".parse().unwrap();
+        world.bullets = [75., 125., 175.]
+            .iter()
+            .map(|x| Bullet {
+                position: na::Point2::from([*x, 25.]),
+                ..Bullet::new(&world.tanks[0])
+            })
+            .map(|bullet| Bullet {
+                direction: na::Vector2::from([1., 0.]),
+                ..bullet
+            })
+            .collect();
+
+        world.advance(0.1);
+
+        let bullets: Vec<_> = world
+            .bullets
+            .iter()
+            .map(|bullet| bullet.position.x)
+            .collect();
+        assert_eq!(bullets, vec![143.]);
+    }
+
+    #[test]
+    fn tanks_start_at_the_maps_spawns() {
+        let mut map = Map::default();
+        map.spawns
+            .push((Player::P2, na::Point2::from([600., 300.])));
+
+        let world = World::with_map(
+            &[Player::P1, Player::P2],
+            Rect::new(0., 0., 1200., 900.),
+            map,
+        );
+
+        assert_eq!(
+            world.tank(Player::P1).unwrap().position,
+            na::Point2::from([1050., 450.])
+        );
+        assert_eq!(
+            world.tank(Player::P2).unwrap().position,
+            na::Point2::from([600., 300.])
+        );
+    }
+
+    fn world() -> World {
+        World::new(&[Player::P1, Player::P2], Rect::new(0., 0., 1200., 900.))
+    }
+}
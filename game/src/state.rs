@@ -1,18 +1,29 @@
 use ggez;
 use ggez::conf::WindowMode;
-use ggez::event;
-use ggez::event::KeyCode;
+use ggez::event::{self, Axis, Button, GamepadId};
 use ggez::graphics;
-use ggez::graphics::screen_coordinates;
 use ggez::graphics::Rect;
 use ggez::nalgebra as na;
-use ggez::Context;
+use ggez::{Context, GameError, GameResult};
+use serde::{Deserialize, Serialize};
 
-use crate::objects::bullet::remove_bullet_if_outside_game_scren;
-use crate::objects::bullet::Bullet;
+use crate::bot::{Bot, Difficulty};
+use crate::camera::Camera;
+use crate::input::{DeviceState, GamepadEvent, Gamepads, InputMap, CONTROLS_PATH};
+use crate::map::Map;
+use crate::network::interpolation::lerp_snapshot;
+use crate::network::protocol::{PlayerInput, Snapshot};
+use crate::network::{INPUT_DELTA, TICK_RATE};
 use crate::objects::Tank;
+use crate::simulation::World;
 
-#[derive(PartialEq)]
+/// Colour of the ground the arena is drawn on.
+pub const BACKGROUND: [f32; 4] = [0.83, 0.69, 0.51, 1.0];
+/// Width of the world the window shows, in world units, at any window size.
+pub const VIRTUAL_WIDTH: f32 = 1200.;
+pub const VIRTUAL_HEIGHT: f32 = 900.;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Player {
     P1,
     P2,
@@ -20,113 +31,266 @@ pub enum Player {
     P4,
 }
 
+impl Player {
+    pub const ALL: [Player; 4] = [Player::P1, Player::P2, Player::P3, Player::P4];
+}
+
+/// A local match: one tank per player, those in `controls` driven from this
+/// machine.
 pub struct MainState {
-    pub tank: Tank,
-    pub enemy: Tank,
-    pub bullet: Option<Bullet>,
-    pub coordinate: Rect,
+    pub world: World,
+    /// The players sharing this machine and how each of them drives.
+    pub controls: Vec<(Player, InputMap)>,
+    /// Drive every other player's tank.
+    pub bots: Vec<Bot>,
+    pub gamepads: Gamepads,
+    /// State before the last simulation step, drawing blends from it to the current one.
+    pub previous: Snapshot,
+    /// Follows the local players round arenas larger than the window.
+    pub camera: Camera,
 }
 
 impl MainState {
-    pub fn new(ctx: &mut Context, player: Player) -> ggez::GameResult<MainState> {
-        let sc = screen_coordinates(ctx);
-        let tank_base = graphics::Image::new(ctx, "/TankBase.png")?;
-        let tank_dimensions = tank_base.dimensions();
-        let turret_width = tank_dimensions.w * 0.4;
-
-        let tank = Tank {
-            position: na::Point2::from([sc.w / 2., sc.h / 2.]),
-            tank_direction: na::Vector2::from([-1., 0.]),
-            tank_rotation: 0.,
-            texture: Some(tank_base.clone()),
-            turret_texture: Some(graphics::Image::new(ctx, "/TankTops.png")?),
-            turret_direction: na::Vector2::from([-1., 0.]),
-            turret_rotation: 0.,
-            turret_rotation_origin: na::Vector2::from([
-                tank_dimensions.w * 0.7,
-                tank_dimensions.h / 2.,
-            ]),
-            player,
-            turret_width,
-        };
-
-        let tank2 = Tank {
-            position: na::Point2::from([sc.w - 100., sc.h - 100.]),
-            tank_direction: na::Vector2::from([-1., 0.]),
-            tank_rotation: 0.,
-            texture: Some(tank_base),
-            turret_texture: Some(graphics::Image::new(ctx, "/TankTops.png")?),
-            turret_direction: na::Vector2::from([-1., 0.]),
-            turret_rotation: 0.,
-            turret_rotation_origin: na::Vector2::from([
-                tank_dimensions.w * 0.7,
-                tank_dimensions.h / 2.,
-            ]),
-            player: Player::P2,
-            turret_width,
-        };
+    /// A match of `player_count` players on the map at `map`, the first
+    /// `local_players` of them at this machine and the rest bots playing at
+    /// `difficulty`.
+    pub fn new(
+        ctx: &mut Context,
+        map: &str,
+        local_players: usize,
+        player_count: usize,
+        difficulty: Difficulty,
+    ) -> GameResult<MainState> {
+        let players = match_players(Player::P1, player_count)?;
+        let controls = local_controls(ctx, local_players, players)?;
+        let bots = players[controls.len()..]
+            .iter()
+            .map(|player| Bot::new(*player, difficulty))
+            .collect();
+        let map = Map::load(ctx, map)?;
+        let mut world = World::with_map(players, map.tiles.bounds(), map);
+        for tank in &mut world.tanks {
+            *tank = Tank::new(ctx, tank.player, tank.position)?;
+        }
 
         let s = MainState {
-            tank,
-            coordinate: sc,
-            bullet: None,
-            enemy: tank2,
+            world,
+            controls,
+            bots,
+            gamepads: Gamepads::default(),
+            previous: Snapshot::default(),
+            camera: Camera::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT),
         };
         Ok(s)
     }
+
+    /// Every local player's input from the keyboard and mouse in `devices` and
+    /// the gamepad their controls read. The mouse is in window coordinates,
+    /// the camera moves it into the world.
+    pub fn inputs(&self, devices: &DeviceState) -> Vec<(Player, PlayerInput)> {
+        let devices = DeviceState {
+            mouse_position: self.camera.screen_to_world(devices.mouse_position),
+            ..devices.clone()
+        };
+        self.controls
+            .iter()
+            .map(|(player, input_map)| {
+                let devices = devices
+                    .clone()
+                    .with_gamepad(&self.gamepads, input_map.gamepad);
+                (*player, input_map.input(&devices, self.world.tank(*player)))
+            })
+            .collect()
+    }
 }
 
 impl event::EventHandler for MainState {
+    /// Runs as many fixed `INPUT_DELTA` steps as real time has passed, so the
+    /// game plays at the same speed whatever the frame rate.
     fn update(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult {
-        self.tank.update_(ctx, &self.enemy)?;
-        self.enemy.update(ctx)?;
-        if let Some(bullet) = &mut self.bullet {
-            let coord = self.coordinate.clone();
-            bullet.update(ctx)?;
-            remove_bullet_if_outside_game_scren(bullet.position, self, coord);
-        } else {
-            let keys = ggez::input::keyboard::pressed_keys(ctx);
-            let left_mouse_button_pressed =
-                ggez::input::mouse::button_pressed(ctx, event::MouseButton::Left);
-            self.fire_bullet(keys, left_mouse_button_pressed);
+        let inputs = self.inputs(&DeviceState::from_context(ctx));
+
+        while ggez::timer::check_update_time(ctx, TICK_RATE) {
+            let mut inputs = inputs.clone();
+            inputs.extend(self.bot_inputs(INPUT_DELTA));
+            self.step(&inputs, INPUT_DELTA);
         }
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult {
-        graphics::clear(ctx, [0.83, 0.69, 0.51, 1.0].into());
-        self.tank.draw(ctx)?;
-        self.enemy.draw(ctx)?;
-        if let Some(bullet) = &mut self.bullet {
-            bullet.draw(ctx)?;
+        let alpha = ggez::timer::remaining_update_time(ctx).as_secs_f32() / INPUT_DELTA;
+        let frame = self.frame(alpha);
+        if let Some(centre) = self.local_centre(&frame) {
+            self.camera.follow(centre, self.world.coordinate);
+        }
+        self.camera.begin_frame(ctx, BACKGROUND.into())?;
+        self.world.map.draw(ctx)?;
+        for tank in &self.world.tanks {
+            let mut drawn = tank.clone();
+            if let Some(tank_snapshot) = frame.tanks.iter().find(|t| t.player == tank.player) {
+                drawn.apply_snapshot(tank_snapshot);
+            }
+            drawn.draw(ctx)?;
+        }
+        for bullet in &frame.bullets {
+            bullet.to_bullet().draw(ctx)?;
         }
         graphics::present(ctx)?;
         Ok(())
     }
+
+    fn resize_event(&mut self, _: &mut Context, width: f32, height: f32) {
+        self.camera.resize(width, height);
+    }
+
+    fn gamepad_button_down_event(&mut self, _: &mut Context, btn: Button, id: GamepadId) {
+        self.gamepads.event(id, GamepadEvent::ButtonDown(btn));
+    }
+
+    fn gamepad_button_up_event(&mut self, _: &mut Context, btn: Button, id: GamepadId) {
+        self.gamepads.event(id, GamepadEvent::ButtonUp(btn));
+    }
+
+    fn gamepad_axis_event(&mut self, _: &mut Context, axis: Axis, value: f32, id: GamepadId) {
+        self.gamepads.event(id, GamepadEvent::Axis(axis, value));
+    }
 }
 
 impl MainState {
-    fn fire_bullet(
-        &mut self,
-        keys: &std::collections::HashSet<KeyCode>,
-        left_mouse_button_pressed: bool,
-    ) {
-        if keys.contains(&KeyCode::Space) || left_mouse_button_pressed {
-            let (x, y) = self.tank.get_turret_end();
-            self.bullet = Some(Bullet {
-                position: na::Point2::from([x, y]),
-                direction: self.tank.turret_direction,
-                rotation: self.tank.turret_rotation,
-                origin: self.tank.turret_rotation_origin,
-            });
+    /// What every bot does for the next `delta` seconds.
+    pub fn bot_inputs(&mut self, delta: f32) -> Vec<(Player, PlayerInput)> {
+        let world = &self.world;
+        self.bots
+            .iter_mut()
+            .map(|bot| (bot.player, bot.input(world, delta)))
+            .collect()
+    }
+
+    /// Advances the match by `delta` seconds with `inputs` driving the local tanks.
+    pub fn step(&mut self, inputs: &[(Player, PlayerInput)], delta: f32) {
+        self.previous = self.snapshot(0);
+        self.world.step(inputs, delta);
+    }
+
+    /// What to draw `alpha` of the way from the previous step to the current one.
+    pub fn frame(&self, alpha: f32) -> Snapshot {
+        lerp_snapshot(&self.previous, &self.snapshot(0), alpha.min(1.))
+    }
+
+    /// Halfway between the local players' tanks in `frame`, where the camera
+    /// looks. `None` once they are all destroyed.
+    pub fn local_centre(&self, frame: &Snapshot) -> Option<na::Point2<f32>> {
+        let positions: Vec<_> = frame
+            .tanks
+            .iter()
+            .filter(|tank| {
+                self.controls
+                    .iter()
+                    .any(|(player, _)| *player == tank.player)
+            })
+            .map(|tank| na::Vector2::from(tank.position))
+            .collect();
+        if positions.is_empty() {
+            return None;
         }
+        let sum = positions
+            .iter()
+            .fold(na::Vector2::zeros(), |sum, p| sum + p);
+        Some(na::Point2::from(sum / positions.len() as f32))
+    }
+
+    /// Simulation state of every tank and bullet, without any textures.
+    pub fn snapshot(&self, tick: u64) -> Snapshot {
+        self.world.snapshot(tick)
+    }
+
+    pub fn apply_snapshot(&mut self, snapshot: &Snapshot) {
+        self.world.apply_snapshot(snapshot);
+    }
+}
+
+/// The first `player_count` players, as long as `player` is one of them.
+pub fn match_players(player: Player, player_count: usize) -> GameResult<&'static [Player]> {
+    if player_count == 0 || player_count > Player::ALL.len() {
+        return Err(GameError::ConfigError(format!(
+            "a match needs 1 to {} players, got {}",
+            Player::ALL.len(),
+            player_count
+        )));
+    }
+
+    let players = &Player::ALL[..player_count];
+    if players.contains(&player) {
+        Ok(players)
+    } else {
+        Err(GameError::ConfigError(format!(
+            "{:?} isn't playing in a {} player match",
+            player, player_count
+        )))
     }
 }
 
+/// Controls for the first `local_players` of `players`. A lone player gets the
+/// whole keyboard from `CONTROLS_PATH`, players sharing the machine get
+/// `InputMap::hot_seat`, or `/controls-p2.toml` and so on when it exists.
+pub fn local_controls(
+    ctx: &mut Context,
+    local_players: usize,
+    players: &[Player],
+) -> GameResult<Vec<(Player, InputMap)>> {
+    if local_players == 0 || local_players > players.len() {
+        return Err(GameError::ConfigError(format!(
+            "1 to {} players can play on this machine, got {}",
+            players.len(),
+            local_players
+        )));
+    }
+    if local_players == 1 {
+        return Ok(vec![(players[0], InputMap::load(ctx, CONTROLS_PATH)?)]);
+    }
+
+    players[..local_players]
+        .iter()
+        .map(|player| {
+            let path = format!("/controls-{:?}.toml", player).to_lowercase();
+            let input_map = InputMap::load_or(ctx, &path, InputMap::hot_seat(*player))?;
+            Ok((*player, input_map))
+        })
+        .collect()
+}
+
+/// Where each of `count` tanks starts: the centre for a single tank, otherwise
+/// spread evenly around an ellipse inside the screen, starting on the right.
+pub fn spawn_positions(count: usize, screen_coord: Rect) -> Vec<na::Point2<f32>> {
+    const MARGIN: f32 = 150.;
+
+    let center = na::Point2::from([
+        screen_coord.x + screen_coord.w / 2.,
+        screen_coord.y + screen_coord.h / 2.,
+    ]);
+    if count == 1 {
+        return vec![center];
+    }
+
+    let radius_x = (screen_coord.w / 2. - MARGIN).max(0.);
+    let radius_y = (screen_coord.h / 2. - MARGIN).max(0.);
+    (0..count)
+        .map(|i| {
+            let angle = i as f32 * 2. * std::f32::consts::PI / count as f32;
+            let (sin, cos) = angle.sin_cos();
+            na::Point2::from([
+                (center.x + radius_x * cos).round(),
+                (center.y + radius_y * sin).round(),
+            ])
+        })
+        .collect()
+}
+
 pub fn window_state_mode() -> WindowMode {
     WindowMode {
-        width: 1200.,
-        height: 900.,
+        width: VIRTUAL_WIDTH,
+        height: VIRTUAL_HEIGHT,
         maximized: false,
         fullscreen_type: ggez::conf::FullscreenType::Windowed,
         borderless: false,
@@ -140,7 +304,17 @@ pub fn window_state_mode() -> WindowMode {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
+    use float_eq::float_eq;
+    use ggez::event::{KeyCode, MouseButton};
+
     use super::*;
+    use crate::map::TileMap;
+    use crate::objects::bullet::Bullet;
+    use crate::objects::bullet::DAMAGE;
+    use crate::objects::weapon::Weapon;
+    use crate::objects::MAX_HEALTH;
 
     #[test]
     fn random_keys_dont_fire_bullet() {
@@ -149,11 +323,11 @@ mod tests {
             .into_iter()
             .collect();
 
-        assert!(main_state.bullet.is_none());
+        assert!(bullets(&main_state).is_empty());
 
-        main_state.fire_bullet(keys, false);
+        main_state.step(&input(keys, false), 0.);
 
-        assert!(main_state.bullet.is_none());
+        assert!(bullets(&main_state).is_empty());
     }
 
     #[test]
@@ -161,11 +335,11 @@ mod tests {
         let mut main_state = main();
         let keys = &vec![KeyCode::Z].into_iter().collect();
 
-        assert!(main_state.bullet.is_none());
+        assert!(bullets(&main_state).is_empty());
 
-        main_state.fire_bullet(keys, true);
+        main_state.step(&input(keys, true), 0.);
 
-        assert!(main_state.bullet.is_some());
+        assert_eq!(bullets(&main_state).len(), 1);
     }
 
     #[test]
@@ -173,11 +347,11 @@ mod tests {
         let mut main_state = main();
         let keys = &vec![KeyCode::Space].into_iter().collect();
 
-        assert!(main_state.bullet.is_none());
+        assert!(bullets(&main_state).is_empty());
 
-        main_state.fire_bullet(keys, false);
+        main_state.step(&input(keys, false), 0.);
 
-        assert!(main_state.bullet.is_some());
+        assert_eq!(bullets(&main_state).len(), 1);
     }
 
     #[test]
@@ -185,44 +359,379 @@ mod tests {
         let mut main_state = main();
         let keys = &Vec::new().into_iter().collect();
 
-        assert!(main_state.bullet.is_none());
+        assert!(bullets(&main_state).is_empty());
+
+        main_state.step(&input(keys, true), 0.);
+
+        let (x, y) = main_state.world.tank(Player::P1).unwrap().get_turret_end();
+        assert_eq!(bullets(&main_state)[0].position, na::Point2::from([x, y]))
+    }
+
+    #[test]
+    fn state_round_trips_through_the_wire_format() {
+        use crate::network::protocol::{decode, encode};
+
+        let mut main_state = main();
+        main_state.step(&input(&Vec::new().into_iter().collect(), true), 0.);
+        main_state.world.tanks[1].health = 75;
+        let bytes = encode(&main_state.snapshot(7)).unwrap();
+
+        let mut restored = main();
+        restored.world.tanks[0].position = na::Point2::from([0., 0.]);
+        restored.world.tanks[1].tank_rotation = 0.;
+        restored.apply_snapshot(&decode(&bytes).unwrap());
+
+        assert_eq!(restored.snapshot(7), main_state.snapshot(7));
+        assert_eq!(restored.world.tanks[1].health, 75);
+        assert_eq!(
+            bullets(&restored)[0].position,
+            bullets(&main_state)[0].position
+        );
+    }
+
+    #[test]
+    fn spawn_positions_for_any_count() {
+        let screen = Rect::new(0., 0., 1200., 900.);
+
+        assert_eq!(
+            spawn_positions(1, screen),
+            vec![na::Point2::from([600., 450.])]
+        );
+        assert_eq!(
+            spawn_positions(2, screen),
+            vec![
+                na::Point2::from([1050., 450.]),
+                na::Point2::from([150., 450.])
+            ]
+        );
+        for count in 1..=Player::ALL.len() {
+            let positions = spawn_positions(count, screen);
+
+            assert_eq!(positions.len(), count);
+            for (i, a) in positions.iter().enumerate() {
+                assert!(screen.contains(*a));
+                for b in positions.iter().skip(i + 1) {
+                    assert_ne!(a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn match_needs_one_to_four_players_including_the_local_one() {
+        assert_eq!(match_players(Player::P1, 1).unwrap(), &[Player::P1]);
+        assert_eq!(match_players(Player::P2, 4).unwrap(), &Player::ALL);
+        assert!(match_players(Player::P1, 0).is_err());
+        assert!(match_players(Player::P1, 5).is_err());
+        assert!(match_players(Player::P3, 2).is_err());
+    }
+
+    #[test]
+    fn bullet_comes_from_the_firing_players_tank() {
+        let mut main_state = main();
+        let (_, fire) = input(&vec![KeyCode::Space].into_iter().collect(), false)[0];
+
+        main_state.step(&[(Player::P2, fire)], 0.);
+
+        assert!(bullets(&main_state).is_empty());
+        assert_eq!(
+            main_state.world.bullets[0].direction,
+            main_state.world.tanks[1].turret_direction
+        );
+    }
+
+    #[test]
+    fn bullet_hitting_a_tank_is_used_up() {
+        let mut main_state = main();
+        main_state.step(
+            &input(&vec![KeyCode::Space].into_iter().collect(), false),
+            0.,
+        );
+        main_state.world.bullets[0].position = main_state.world.tanks[1].position;
+
+        main_state.world.advance(0.);
+
+        assert!(bullets(&main_state).is_empty());
+        assert_eq!(main_state.world.tanks[1].health, MAX_HEALTH - DAMAGE);
+    }
+
+    #[test]
+    fn destroyed_tanks_are_removed() {
+        let mut main_state = main();
+        main_state.world.tanks[1].health = DAMAGE;
+        main_state.step(
+            &input(&vec![KeyCode::Space].into_iter().collect(), false),
+            0.,
+        );
+        main_state.world.bullets[0].position = main_state.world.tanks[1].position;
+
+        main_state.world.advance(0.);
+
+        assert_eq!(main_state.world.tanks.len(), 1);
+        assert_eq!(main_state.world.tanks[0].player, Player::P1);
+    }
+
+    #[test]
+    fn holding_fire_shoots_at_the_fire_rate() {
+        let mut main_state = main();
+        let keys = &vec![KeyCode::Space].into_iter().collect();
+
+        main_state.step(&input(keys, false), 0.);
+        main_state.step(&input(keys, false), 0.);
+        assert_eq!(bullets(&main_state).len(), 1);
+
+        main_state.world.tanks[0].weapon.cool_down(1.);
+        main_state.step(&input(keys, false), 0.);
+        assert_eq!(bullets(&main_state).len(), 2);
+    }
+
+    #[test]
+    fn every_bullet_in_flight_is_in_the_snapshot() {
+        let mut main_state = main();
+        main_state.step(
+            &input(&vec![KeyCode::Space].into_iter().collect(), false),
+            0.,
+        );
+        main_state.world.tanks[0].weapon.cool_down(1.);
+        main_state.step(
+            &input(&vec![KeyCode::Space].into_iter().collect(), false),
+            0.,
+        );
+        let bullet = main_state.world.tanks[1].fire().unwrap();
+        main_state.world.bullets.push(bullet);
 
-        main_state.fire_bullet(keys, true);
+        let snapshot = main_state.snapshot(1);
+        let mut restored = main();
+        restored.apply_snapshot(&snapshot);
 
+        assert_eq!(snapshot.bullets.len(), 3);
+        assert_eq!(bullets(&restored).len(), 2);
+        assert_eq!(restored.world.bullets[2].owner, Player::P2);
+    }
+
+    #[test]
+    fn frames_blend_from_the_previous_step() {
+        let mut main_state = main();
+        main_state.previous = main_state.snapshot(0);
+        main_state.world.tanks[0].position = na::Point2::from([310., 300.]);
+
+        assert_eq!(main_state.frame(0.).tanks[0].position, [300., 300.]);
+        assert_eq!(main_state.frame(0.5).tanks[0].position, [305., 300.]);
+        assert_eq!(main_state.frame(2.).tanks[0].position, [310., 300.]);
+    }
+
+    #[test]
+    fn gamepad_events_drive_and_aim_the_tank() {
+        let mut main_state = main();
+        main_state.controls = vec![(Player::P1, InputMap::gamepad(1))];
+        // the first gamepad belongs to someone else
+        main_state
+            .gamepads
+            .apply(0, GamepadEvent::Axis(Axis::LeftStickY, 1.));
+        main_state
+            .gamepads
+            .apply(1, GamepadEvent::Axis(Axis::LeftStickY, -1.));
+        main_state
+            .gamepads
+            .apply(1, GamepadEvent::Axis(Axis::RightStickY, 1.));
+        main_state
+            .gamepads
+            .apply(1, GamepadEvent::ButtonDown(Button::RightTrigger2));
+
+        let inputs = main_state.inputs(&DeviceState::default());
+        main_state.step(&inputs, INPUT_DELTA);
+        let input = inputs[0].1;
+
+        assert!(input.backward);
+        assert!(!input.forward);
+        assert_eq!(bullets(&main_state).len(), 1);
+        let turret = main_state.world.tank(Player::P1).unwrap().turret_direction;
+        assert!(float_eq!(turret.x, 0., abs <= 0.01));
+        assert!(float_eq!(turret.y, -1., abs <= 0.01));
+    }
+
+    #[test]
+    fn every_local_player_drives_their_own_tank() {
+        let mut main_state = MainState {
+            controls: vec![
+                (Player::P1, InputMap::hot_seat(Player::P1)),
+                (Player::P2, InputMap::hot_seat(Player::P2)),
+            ],
+            ..main()
+        };
+        let devices = DeviceState {
+            keys: vec![KeyCode::W, KeyCode::RControl].into_iter().collect(),
+            ..DeviceState::default()
+        };
+
+        let inputs = main_state.inputs(&devices);
+        main_state.step(&inputs, INPUT_DELTA);
+
+        assert_eq!(inputs.len(), 2);
+        assert!(inputs[0].1.forward && !inputs[0].1.fire);
+        assert!(!inputs[1].1.forward && inputs[1].1.fire);
+        assert_ne!(
+            main_state.world.tank(Player::P1).unwrap().position,
+            main_tank().position
+        );
         assert_eq!(
-            main_state.bullet.unwrap().position,
-            na::Point2::from([299.22873, 304.94016])
-        )
+            main_state
+                .world
+                .bullets
+                .iter()
+                .filter(|bullet| bullet.owner == Player::P2)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn bots_drive_the_players_nobody_controls() {
+        let mut main_state = MainState {
+            bots: vec![Bot::new(Player::P2, Difficulty::Hard)],
+            ..main()
+        };
+
+        let inputs = main_state.bot_inputs(INPUT_DELTA);
+        main_state.step(&inputs, INPUT_DELTA);
+
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].0, Player::P2);
+        assert_eq!(
+            main_state
+                .world
+                .bullets
+                .iter()
+                .filter(|bullet| bullet.owner == Player::P2)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn the_mouse_aims_at_the_world_under_it() {
+        let mut main_state = main();
+        main_state.camera.follow(
+            na::Point2::from([1000., 800.]),
+            Rect::new(0., 0., 2000., 1800.),
+        );
+        let devices = DeviceState {
+            mouse_position: na::Point2::from([10., 20.]),
+            ..DeviceState::default()
+        };
+
+        assert_eq!(main_state.inputs(&devices)[0].1.aim, [410., 370.]);
+    }
+
+    #[test]
+    fn aiming_follows_the_mouse_in_a_resized_window() {
+        let mut main_state = main();
+        main_state.camera.resize(2400., 2000.);
+        // twice the size, with 100 pixel bars above and below
+        let devices = DeviceState {
+            mouse_position: na::Point2::from([20., 140.]),
+            ..DeviceState::default()
+        };
+
+        assert_eq!(main_state.inputs(&devices)[0].1.aim, [10., 20.]);
+    }
+
+    #[test]
+    fn the_camera_looks_between_local_players() {
+        let mut main_state = main();
+        let frame = main_state.snapshot(0);
+        assert_eq!(
+            main_state.local_centre(&frame),
+            Some(na::Point2::from([300., 300.]))
+        );
+
+        main_state
+            .controls
+            .push((Player::P2, InputMap::hot_seat(Player::P2)));
+        assert_eq!(
+            main_state.local_centre(&frame),
+            Some(na::Point2::from([200., 200.]))
+        );
+
+        main_state.controls.clear();
+        assert_eq!(main_state.local_centre(&frame), None);
+    }
+
+    fn input(
+        keys: &HashSet<KeyCode>,
+        left_mouse_button_pressed: bool,
+    ) -> Vec<(Player, PlayerInput)> {
+        let mut devices = DeviceState {
+            keys: keys.clone(),
+            ..DeviceState::default()
+        };
+        if left_mouse_button_pressed {
+            devices.mouse_buttons.insert(MouseButton::Left);
+        }
+        let input = PlayerInput {
+            aim: [0., 0.],
+            ..InputMap::default().input(&devices, None)
+        };
+        vec![(Player::P1, input)]
+    }
+
+    /// Bullets fired by the local player's tank.
+    fn bullets(main_state: &MainState) -> Vec<&Bullet> {
+        main_state
+            .world
+            .bullets
+            .iter()
+            .filter(|bullet| bullet.owner == Player::P1)
+            .collect()
     }
 
     fn main() -> MainState {
         MainState {
-            tank: Tank {
-                position: na::Point2::from([300., 300.]),
-                tank_direction: na::Vector2::from([1., 1.]),
-                tank_rotation: 30.,
-                texture: None,
-                turret_texture: None,
-                turret_direction: na::Vector2::from([1., 1.]),
-                turret_rotation: 30.,
-                turret_rotation_origin: na::Vector2::from([1., 1.]),
-                player: crate::state::Player::P1,
-                turret_width: 5.,
-            },
-            bullet: None,
-            coordinate: coord(),
-            enemy: Tank {
-                position: na::Point2::from([100., 100.]),
-                tank_direction: na::Vector2::from([1., 1.]),
-                tank_rotation: 30.,
-                texture: None,
-                turret_texture: None,
-                turret_direction: na::Vector2::from([1., 1.]),
-                turret_rotation: 30.,
-                turret_rotation_origin: na::Vector2::from([1., 1.]),
-                player: crate::state::Player::P2,
-                turret_width: 5.,
+            world: World {
+                tanks: vec![main_tank(), enemy_tank()],
+                bullets: Vec::new(),
+                coordinate: coord(),
+                map: TileMap::default(),
             },
+            controls: vec![(Player::P1, InputMap::default())],
+            bots: Vec::new(),
+            gamepads: Gamepads::default(),
+            previous: Snapshot::default(),
+            camera: Camera::new(1200., 900.),
+        }
+    }
+
+    fn main_tank() -> Tank {
+        Tank {
+            position: na::Point2::from([300., 300.]),
+            tank_direction: na::Vector2::from([1., 1.]),
+            tank_rotation: 30.,
+            texture: None,
+            turret_texture: None,
+            turret_direction: na::Vector2::from([1., 1.]),
+            turret_rotation: 30.,
+            player: crate::state::Player::P1,
+            turret_width: 5.,
+            health: MAX_HEALTH,
+            armor: 0,
+            weapon: Weapon::default(),
+        }
+    }
+
+    fn enemy_tank() -> Tank {
+        Tank {
+            position: na::Point2::from([100., 100.]),
+            tank_direction: na::Vector2::from([1., 1.]),
+            tank_rotation: 30.,
+            texture: None,
+            turret_texture: None,
+            turret_direction: na::Vector2::from([1., 1.]),
+            turret_rotation: 30.,
+            player: crate::state::Player::P2,
+            turret_width: 5.,
+            health: MAX_HEALTH,
+            armor: 0,
+            weapon: Weapon::default(),
         }
     }
 
@@ -0,0 +1,248 @@
+use image::RgbaImage;
+use std::fmt;
+
+/// How close a screenshot has to be to its reference to pass.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Comparison {
+    /// Every byte has to match.
+    #[default]
+    Exact,
+    /// Channels may be off by up to `channel`, and up to `max_differing` of
+    /// the pixels, from 0 to 1, may be off by more.
+    Tolerance { channel: u8, max_differing: f64 },
+    /// The mean structural similarity (SSIM) of the two images' brightness has
+    /// to be at least `min_ssim`. Small shifts in antialiasing barely lower
+    /// it, while missing or moved shapes do.
+    Perceptual { min_ssim: f64 },
+}
+
+/// What a comparison measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Score {
+    /// Share of pixels off by more than the tolerance, from 0 to 1.
+    DifferingPixels(f64),
+    /// Mean SSIM, 1 for identical images.
+    Ssim(f64),
+}
+
+/// Side of the square windows SSIM is averaged over.
+const SSIM_WINDOW: u32 = 8;
+const SSIM_C1: f64 = (0.01 * 255.) * (0.01 * 255.);
+const SSIM_C2: f64 = (0.03 * 255.) * (0.03 * 255.);
+
+impl Comparison {
+    /// Measures `actual` against `expected`. Images of different sizes have
+    /// nothing in common and score as badly as possible.
+    pub fn score(&self, expected: &RgbaImage, actual: &RgbaImage) -> Score {
+        let same_size = expected.dimensions() == actual.dimensions();
+        match *self {
+            Comparison::Exact => Score::DifferingPixels(if same_size {
+                differing_pixels(expected, actual, 0)
+            } else {
+                1.
+            }),
+            Comparison::Tolerance { channel, .. } => Score::DifferingPixels(if same_size {
+                differing_pixels(expected, actual, channel)
+            } else {
+                1.
+            }),
+            Comparison::Perceptual { .. } => Score::Ssim(if same_size {
+                ssim(expected, actual)
+            } else {
+                0.
+            }),
+        }
+    }
+
+    pub fn accepts(&self, score: Score) -> bool {
+        match (*self, score) {
+            (Comparison::Exact, Score::DifferingPixels(differing)) => differing == 0.,
+            (Comparison::Tolerance { max_differing, .. }, Score::DifferingPixels(differing)) => {
+                differing <= max_differing
+            }
+            (Comparison::Perceptual { min_ssim }, Score::Ssim(ssim)) => ssim >= min_ssim,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Comparison::Exact => write!(f, "identical pixels"),
+            Comparison::Tolerance {
+                channel,
+                max_differing,
+            } => write!(
+                f,
+                "at most {}% of pixels off by more than {}",
+                max_differing * 100.,
+                channel
+            ),
+            Comparison::Perceptual { min_ssim } => write!(f, "SSIM of at least {}", min_ssim),
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::DifferingPixels(differing) => {
+                write!(f, "{:.3}% of pixels differ", differing * 100.)
+            }
+            Score::Ssim(ssim) => write!(f, "SSIM {:.4}", ssim),
+        }
+    }
+}
+
+fn differing_pixels(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> f64 {
+    let differing = expected
+        .pixels()
+        .zip(actual.pixels())
+        .filter(|(e, a)| {
+            e.0.iter()
+                .zip(a.0.iter())
+                .any(|(e, a)| (i16::from(*e) - i16::from(*a)).abs() > i16::from(tolerance))
+        })
+        .count();
+    let total = expected.pixels().len().max(1);
+
+    differing as f64 / total as f64
+}
+
+/// Mean SSIM over `SSIM_WINDOW` sized tiles of the images' luma.
+fn ssim(expected: &RgbaImage, actual: &RgbaImage) -> f64 {
+    let (w, h) = expected.dimensions();
+    let mut total = 0.;
+    let mut windows = 0;
+
+    for top in (0..h).step_by(SSIM_WINDOW as usize) {
+        for left in (0..w).step_by(SSIM_WINDOW as usize) {
+            let pixels = (top..(top + SSIM_WINDOW).min(h))
+                .flat_map(|y| (left..(left + SSIM_WINDOW).min(w)).map(move |x| (x, y)))
+                .map(|(x, y)| (luma(expected, x, y), luma(actual, x, y)))
+                .collect::<Vec<_>>();
+            total += window_ssim(&pixels);
+            windows += 1;
+        }
+    }
+
+    if windows == 0 {
+        1.
+    } else {
+        total / f64::from(windows)
+    }
+}
+
+fn window_ssim(pixels: &[(f64, f64)]) -> f64 {
+    let n = pixels.len() as f64;
+    let mean_e = pixels.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_a = pixels.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut var_e, mut var_a, mut covariance) = (0., 0., 0.);
+    for (e, a) in pixels {
+        var_e += (e - mean_e) * (e - mean_e) / n;
+        var_a += (a - mean_a) * (a - mean_a) / n;
+        covariance += (e - mean_e) * (a - mean_a) / n;
+    }
+
+    ((2. * mean_e * mean_a + SSIM_C1) * (2. * covariance + SSIM_C2))
+        / ((mean_e * mean_e + mean_a * mean_a + SSIM_C1) * (var_e + var_a + SSIM_C2))
+}
+
+fn luma(image: &RgbaImage, x: u32, y: u32) -> f64 {
+    let [r, g, b, _] = image.get_pixel(x, y).0;
+    0.299 * f64::from(r) + 0.587 * f64::from(g) + 0.114 * f64::from(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn exact_comparison_fails_on_any_difference() {
+        let expected = square(16, 4);
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, Rgba([1, 0, 0, 255]));
+
+        let score = Comparison::Exact.score(&expected, &actual);
+
+        assert_eq!(score, Score::DifferingPixels(1. / 256.));
+        assert!(!Comparison::Exact.accepts(score));
+        assert!(Comparison::Exact.accepts(Comparison::Exact.score(&expected, &expected)));
+    }
+
+    #[test]
+    fn tolerance_allows_small_channel_changes_on_few_pixels() {
+        let expected = square(16, 4);
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        actual.put_pixel(0, 1, Rgba([3, 3, 3, 255]));
+        let comparison = Comparison::Tolerance {
+            channel: 3,
+            max_differing: 0.01,
+        };
+
+        let score = comparison.score(&expected, &actual);
+
+        assert_eq!(score, Score::DifferingPixels(1. / 256.));
+        assert!(comparison.accepts(score));
+        actual.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+        actual.put_pixel(2, 0, Rgba([255, 255, 255, 255]));
+        assert!(!comparison.accepts(comparison.score(&expected, &actual)));
+    }
+
+    #[test]
+    fn perceptual_comparison_tells_a_soft_edge_from_a_changed_shape() {
+        let expected = square(32, 6);
+        let mut blurred = expected.clone();
+        for y in 6..26 {
+            blurred.put_pixel(5, y, Rgba([128, 128, 128, 255]));
+        }
+        let shrunk = square(32, 10);
+        let comparison = Comparison::Perceptual { min_ssim: 0.9 };
+
+        assert!(comparison.accepts(comparison.score(&expected, &blurred)));
+        let score = comparison.score(&expected, &shrunk);
+        assert!(!comparison.accepts(score));
+        assert_eq!(comparison.score(&expected, &expected), Score::Ssim(1.));
+    }
+
+    #[test]
+    fn different_sizes_never_pass() {
+        let comparison = Comparison::Perceptual { min_ssim: 0. };
+
+        let score = comparison.score(&square(16, 4), &square(32, 4));
+
+        assert_eq!(score, Score::Ssim(0.));
+        assert!(!Comparison::Exact.accepts(Comparison::Exact.score(&square(16, 4), &square(32, 4))));
+    }
+
+    #[test]
+    fn reports_scores_readably() {
+        assert_eq!(
+            Score::DifferingPixels(0.0125).to_string(),
+            "1.250% of pixels differ"
+        );
+        assert_eq!(Score::Ssim(0.97).to_string(), "SSIM 0.9700");
+        assert_eq!(
+            Comparison::Tolerance {
+                channel: 2,
+                max_differing: 0.5
+            }
+            .to_string(),
+            "at most 50% of pixels off by more than 2"
+        );
+    }
+
+    /// A `size` wide black image with a white square from `start` to `size - start`.
+    fn square(size: u32, start: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, y| {
+            if (start..size - start).contains(&x) && (start..size - start).contains(&y) {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        })
+    }
+}
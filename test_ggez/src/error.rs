@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::compare::{Comparison, Score};
+use crate::mask::Area;
+use crate::UPDATE_VAR;
+
+/// Why a screenshot test failed.
+#[derive(Debug)]
+pub enum ScreenshotError {
+    /// The reference screenshot couldn't be read.
+    LoadIoError {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A screenshot or its folder couldn't be written.
+    SaveIoError {
+        path: PathBuf,
+        source: io::Error,
+    },
+    EncodingError {
+        path: PathBuf,
+        source: png::EncodingError,
+    },
+    /// The reference screenshot isn't an image `image` can open.
+    DecodingError {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    CaptureError(ggez::GameError),
+    /// The game quit before the screenshot was taken.
+    NotCaptured,
+    /// The area to compare reaches outside the `width` by `height` capture.
+    CropOutsideCapture {
+        crop: Area,
+        width: u32,
+        height: u32,
+    },
+    /// There is no reference yet. The capture was saved to `actual`.
+    NoReferenceScreenshot {
+        reference: PathBuf,
+        actual: PathBuf,
+    },
+    /// The capture wasn't close enough to the reference. It was saved to
+    /// `actual`, next to a `diff` if the two are the same size.
+    ScreenshotMismatch {
+        reference: PathBuf,
+        actual: PathBuf,
+        diff: Option<PathBuf>,
+        score: Score,
+        comparison: Comparison,
+    },
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScreenshotError::LoadIoError { path, source } => {
+                write!(f, "couldn't read {}: {}", path.display(), source)
+            }
+            ScreenshotError::SaveIoError { path, source } => {
+                write!(f, "couldn't save {}: {}", path.display(), source)
+            }
+            ScreenshotError::EncodingError { path, source } => {
+                write!(f, "couldn't encode {}: {}", path.display(), source)
+            }
+            ScreenshotError::DecodingError { path, source } => {
+                write!(f, "couldn't decode {}: {}", path.display(), source)
+            }
+            ScreenshotError::CaptureError(source) => {
+                write!(f, "couldn't capture the screenshot: {}", source)
+            }
+            ScreenshotError::NotCaptured => {
+                write!(f, "the game quit before the screenshot was taken")
+            }
+            ScreenshotError::CropOutsideCapture {
+                crop,
+                width,
+                height,
+            } => write!(
+                f,
+                "the {} by {} crop at ({}, {}) reaches outside the {} by {} capture",
+                crop.w, crop.h, crop.x, crop.y, width, height
+            ),
+            ScreenshotError::NoReferenceScreenshot { reference, actual } => write!(
+                f,
+                "there is no reference at {}, the screenshot was saved to {}; run with {}=1 to use it",
+                reference.display(),
+                actual.display(),
+                UPDATE_VAR
+            ),
+            ScreenshotError::ScreenshotMismatch {
+                reference,
+                actual,
+                diff,
+                score,
+                comparison,
+            } => {
+                write!(
+                    f,
+                    "the screenshot doesn't match {}: {}, expected {}; it was saved to {}",
+                    reference.display(),
+                    score,
+                    comparison,
+                    actual.display()
+                )?;
+                match diff {
+                    Some(diff) => write!(f, " with the differences in {}", diff.display()),
+                    None => write!(f, ", its size differs from the reference"),
+                }
+            }
+        }
+    }
+}
+
+impl Error for ScreenshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScreenshotError::LoadIoError { source, .. }
+            | ScreenshotError::SaveIoError { source, .. } => Some(source),
+            ScreenshotError::EncodingError { source, .. } => Some(source),
+            ScreenshotError::DecodingError { source, .. } => Some(source),
+            ScreenshotError::CaptureError(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_the_files_to_look_at() {
+        let error = ScreenshotError::ScreenshotMismatch {
+            reference: PathBuf::from("test_resources/tank/expected.png"),
+            actual: PathBuf::from("target/test_ggez/tank/actual.png"),
+            diff: Some(PathBuf::from("target/test_ggez/tank/diff.png")),
+            score: Score::DifferingPixels(0.5),
+            comparison: Comparison::Exact,
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "the screenshot doesn't match test_resources/tank/expected.png: \
+             50.000% of pixels differ, expected identical pixels; \
+             it was saved to target/test_ggez/tank/actual.png \
+             with the differences in target/test_ggez/tank/diff.png"
+        );
+    }
+
+    #[test]
+    fn keeps_the_underlying_cause() {
+        let error = ScreenshotError::SaveIoError {
+            path: PathBuf::from("target/test_ggez/tank"),
+            source: io::Error::new(io::ErrorKind::PermissionDenied, "permission denied"),
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "couldn't save target/test_ggez/tank: permission denied"
+        );
+        assert_eq!(error.source().unwrap().to_string(), "permission denied");
+    }
+}
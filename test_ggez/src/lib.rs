@@ -1,164 +1,180 @@
-use ggez::graphics::set_fullscreen;
-use image::DynamicImage as Image;
-use image::GenericImageView;
-use image::ImageBuffer;
-use image::Rgba;
-use std::fs;
-use std::fs::OpenOptions;
+use ggez::graphics::{self, Canvas};
+use image::{ImageError, Rgba, RgbaImage};
+use std::fs::{self, OpenOptions};
 use std::io::BufWriter;
 use std::path::Path;
 
+pub mod compare;
+mod error;
+pub mod mask;
 pub mod screenshot;
 
+use compare::Comparison;
+pub use error::ScreenshotError;
 use ggez::{event, Context};
-use screenshot::get_screenshot;
-
-#[derive(Debug, Clone)]
-pub enum ScreenshotError {
-    LoadIoError,
-    SaveIoError,
-    EncodingError,
-    DecodingError,
-    CaptureError,
-    NoReferenceScreenshot((u32, u32, Vec<u8>)),
-    ScreenshotMismatch(u32, u32, Image, Image),
-}
+use mask::{Area, Mask, MASK_COLOUR};
+
+/// Set to `1` to save every capture as its test's new reference instead of
+/// comparing it.
+pub const UPDATE_VAR: &str = "TEST_GGEZ_UPDATE";
 
-fn write_image(filename: &str, buff: &[u8], w: u32, h: u32) -> Result<(), ScreenshotError> {
+fn write_image(path: &Path, image: &RgbaImage) -> Result<(), ScreenshotError> {
+    let folder = path.parent().unwrap_or_else(|| Path::new(""));
+    fs::create_dir_all(folder).map_err(|source| ScreenshotError::SaveIoError {
+        path: folder.to_owned(),
+        source,
+    })?;
     let file = OpenOptions::new()
         .write(true)
-        .append(false)
         .create(true)
-        .open(filename)
-        .unwrap();
-    let ref mut wt = BufWriter::new(file);
-    let mut encoder = png::Encoder::new(wt, w, h);
+        .truncate(true)
+        .open(path)
+        .map_err(|source| ScreenshotError::SaveIoError {
+            path: path.to_owned(),
+            source,
+        })?;
+    let encoding_error = |source| ScreenshotError::EncodingError {
+        path: path.to_owned(),
+        source,
+    };
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width(), image.height());
     encoder.set_color(png::ColorType::RGBA);
     encoder.set_depth(png::BitDepth::Eight);
-    let mut writer = encoder.write_header().unwrap();
-    writer.write_image_data(&buff).unwrap();
-    Ok(())
+    encoder
+        .write_header()
+        .map_err(encoding_error)?
+        .write_image_data(image)
+        .map_err(encoding_error)
 }
 
-fn load_reference(path: &str) -> Result<Image, ScreenshotError> {
-    if Path::new(path).exists() {
-        println!("Loading image: {}", path);
-
-        image::open(path).or(Err(ScreenshotError::LoadIoError))
-    } else {
-        println!("Failed to load test");
-        Err(ScreenshotError::LoadIoError)
+fn load_reference(path: &Path) -> Result<RgbaImage, ScreenshotError> {
+    match image::open(path) {
+        Ok(image) => Ok(image.to_rgba8()),
+        Err(ImageError::IoError(source)) => Err(ScreenshotError::LoadIoError {
+            path: path.to_owned(),
+            source,
+        }),
+        Err(source) => Err(ScreenshotError::DecodingError {
+            path: path.to_owned(),
+            source,
+        }),
     }
 }
 
-fn compare_screenshot_images(
+/// The pixels that differ, black where they match and magenta where `mask`
+/// hides them.
+fn diff_images(actual: &RgbaImage, expected: &RgbaImage, mask: &Mask) -> RgbaImage {
+    RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let actual_pixel = *actual.get_pixel(x, y);
+        if mask.hides(x, y) {
+            MASK_COLOUR
+        } else if actual_pixel == *expected.get_pixel(x, y) {
+            Rgba([u8::MIN; 4])
+        } else {
+            actual_pixel
+        }
+    })
+}
+
+fn updating() -> bool {
+    matches!(std::env::var(UPDATE_VAR).as_deref(), Ok("1"))
+}
+
+/// Compares a `w` by `h` RGBA capture with the reference screenshot saved for
+/// `path`, passing if it is as close as `comparison` asks where `mask` doesn't
+/// hide it.
+///
+/// References live in `test_resources/<path>/expected.png` and are only
+/// written when `UPDATE_VAR` is set. Failures leave `actual.png` and
+/// `diff.png` in `target/test_ggez/<path>/`.
+pub fn screenshot_test(
+    path: &str,
     w: u32,
     h: u32,
-    reference_image: Image,
-    actual_image: Image,
+    captured_buff: Vec<u8>,
+    comparison: &Comparison,
+    mask: &Mask,
 ) -> Result<(), ScreenshotError> {
-    println!("Comparing images");
-    if reference_image.dimensions() == actual_image.dimensions()
-        && reference_image.to_rgba8() == actual_image.to_rgba8()
-    {
-        println!("eq images");
+    let actual = RgbaImage::from_vec(w, h, captured_buff).ok_or_else(|| {
+        ScreenshotError::CaptureError(ggez::GameError::RenderError(format!(
+            "the capture doesn't hold {} by {} pixels",
+            w, h
+        )))
+    })?;
+    if let Some(crop) = mask.crop.filter(|crop| !crop.fits(w, h)) {
+        return Err(ScreenshotError::CropOutsideCapture {
+            crop,
+            width: w,
+            height: h,
+        });
+    }
+    let reference = Path::new("test_resources").join(path).join("expected.png");
+    let output = Path::new("target/test_ggez").join(path);
+
+    if updating() {
+        write_image(&reference, &actual)?;
+        println!("Updated reference: {}", reference.display());
         Ok(())
     } else {
-        println!("diff images");
-        Err(ScreenshotError::ScreenshotMismatch(
-            w,
-            h,
-            actual_image,
-            reference_image,
-        ))
+        check(&reference, &output, &actual, comparison, mask)
     }
 }
 
-fn diff_images(actual: &Image, expected: &Image) -> Result<Vec<u8>, ScreenshotError> {
-    let pixels: Vec<(u32, u32, Rgba<u8>)> = actual
-        .pixels()
-        .zip(expected.pixels())
-        .map(|(actual_pixel, expected_pixel)| {
-            if actual_pixel == expected_pixel {
-                (actual_pixel.0, actual_pixel.1, Rgba([u8::MIN; 4]))
-            } else {
-                actual_pixel
-            }
-        })
-        .collect();
-
-    Ok(pixels.into_iter().map(|b| b.2 .0).flatten().collect())
-}
-
-fn handle_screenshot_error(
-    output_path: &str,
-    screenshot_error: ScreenshotError,
+/// Compares `actual` with the `reference` file, leaving what failed in
+/// `output`.
+fn check(
+    reference: &Path,
+    output: &Path,
+    actual: &RgbaImage,
+    comparison: &Comparison,
+    mask: &Mask,
 ) -> Result<(), ScreenshotError> {
-    fs::create_dir_all(output_path).unwrap();
+    let actual_path = output.join("actual.png");
+    if !reference.exists() {
+        write_image(&actual_path, actual)?;
+        return Err(ScreenshotError::NoReferenceScreenshot {
+            reference: reference.to_owned(),
+            actual: actual_path,
+        });
+    }
 
-    match screenshot_error.clone() {
-        ScreenshotError::NoReferenceScreenshot((w, h, buff)) => {
-            println!("load ref err");
-            write_image(&(output_path.to_string() + "expected.png"), &buff, w, h)?;
-        }
-        ScreenshotError::ScreenshotMismatch(w, h, ref actual, ref expected) => {
-            println!("diff images err");
-            let diff_image = diff_images(&actual, &expected)?;
-            let actual = actual
-                .pixels()
-                .map(|b| b.2 .0)
-                .flatten()
-                .collect::<Vec<u8>>();
-            let expected = expected
-                .pixels()
-                .map(|b| b.2 .0)
-                .flatten()
-                .collect::<Vec<u8>>();
-            write_image(&(output_path.to_string() + "actual.png"), &actual, w, h)?;
-            write_image(&(output_path.to_string() + "expected.png"), &expected, w, h)?;
-            write_image(&(output_path.to_string() + "diff.png"), &diff_image, w, h)?;
-        }
-        _ => {}
+    let expected = load_reference(reference)?;
+    let score = comparison.score(&mask.apply(&expected), &mask.apply(actual));
+    if comparison.accepts(score) {
+        return Ok(());
     }
-    Err(screenshot_error)
-}
 
-pub fn screenshot_test(path: &str) -> Result<(), ScreenshotError> {
-    let current_dir = std::env::current_dir().unwrap();
-    let resouces_path = current_dir
-        .to_str()
-        .map(|s| String::from(s) + "/test_resources/" + path + "/")
-        .unwrap_or_default();
-    let expected_path = resouces_path.clone() + "expected.png";
-
-    let (w, h, captured_buff) = get_screenshot();
-    let captured_image_buff =
-        ImageBuffer::from_vec(w as u32, h as u32, captured_buff.clone()).unwrap();
-    let capture_image = Image::ImageRgba8(captured_image_buff);
-
-    fs::create_dir_all(&resouces_path).unwrap();
-
-    let test = match load_reference(&expected_path) {
-        Ok(reference_image) => Ok((reference_image, capture_image)),
-        Err(_) => Err(ScreenshotError::NoReferenceScreenshot((
-            w as u32,
-            h as u32,
-            captured_buff,
-        ))),
-    }
-    .and_then(|(reference_image, captured_image)| {
-        compare_screenshot_images(w as u32, h as u32, reference_image, captured_image)
+    write_image(&actual_path, actual)?;
+    let diff = if expected.dimensions() == actual.dimensions() {
+        let diff_path = output.join("diff.png");
+        write_image(&diff_path, &diff_images(actual, &expected, mask))?;
+        Some(diff_path)
+    } else {
+        None
+    };
+    Err(ScreenshotError::ScreenshotMismatch {
+        reference: reference.to_owned(),
+        actual: actual_path,
+        diff,
+        score,
+        comparison: *comparison,
     })
-    .or_else(|err| handle_screenshot_error(&resouces_path, err));
-
-    assert!(test.is_ok());
-    Ok(())
 }
 
+/// Draws `element` into an offscreen canvas and compares it with the
+/// reference screenshot on the third frame. Only the game is captured and no
+/// desktop is needed, so tests also run under a software renderer or Xvfb.
+///
+/// Run it with `event::run` and check `finish` afterwards.
 pub struct TestState<T: ggez::event::EventHandler> {
     element: T,
     frame_count: usize,
     test_name: String,
+    comparison: Comparison,
+    mask: Mask,
+    canvas: Option<Canvas>,
+    result: Option<Result<(), ScreenshotError>>,
 }
 
 impl<T: ggez::event::EventHandler> TestState<T> {
@@ -167,17 +183,58 @@ impl<T: ggez::event::EventHandler> TestState<T> {
             frame_count: 0,
             element,
             test_name: test_name.to_owned(),
+            comparison: Comparison::default(),
+            mask: Mask::default(),
+            canvas: None,
+            result: None,
         }
     }
+
+    /// Passes the test on screenshots `comparison` accepts instead of only
+    /// identical ones.
+    pub fn with_comparison(mut self, comparison: Comparison) -> Self {
+        self.comparison = comparison;
+        self
+    }
+
+    /// Leaves `area` of the screenshot, in pixels, out of the comparison.
+    pub fn ignoring(mut self, area: Area) -> Self {
+        self.mask.ignored.push(area);
+        self
+    }
+
+    /// Compares only `area` of the screenshot, in pixels. It has to lie within
+    /// the capture.
+    pub fn cropped_to(mut self, area: Area) -> Self {
+        self.mask.crop = Some(area);
+        self
+    }
+
+    /// How the screenshot test went.
+    pub fn finish(self) -> Result<(), ScreenshotError> {
+        self.result.unwrap_or(Err(ScreenshotError::NotCaptured))
+    }
 }
 
 impl<T: ggez::event::EventHandler> ggez::event::EventHandler for TestState<T> {
     fn update(&mut self, ctx: &mut Context) -> ggez::GameResult {
-        if self.frame_count == 0 {
-            set_fullscreen(ctx, ggez::conf::FullscreenType::Desktop)?;
-            self.frame_count += 1;
-        } else if self.frame_count == 2 {
-            screenshot_test(&self.test_name).unwrap();
+        if self.frame_count == 2 {
+            if let Some(canvas) = &self.canvas {
+                self.result = Some(
+                    screenshot::capture(ctx, canvas)
+                        .map_err(ScreenshotError::CaptureError)
+                        .and_then(|(w, h, captured_buff)| {
+                            screenshot_test(
+                                &self.test_name,
+                                w,
+                                h,
+                                captured_buff,
+                                &self.comparison,
+                                &self.mask,
+                            )
+                        }),
+                );
+            }
             self.frame_count += 1;
         } else if self.frame_count > 4 {
             event::quit(ctx);
@@ -189,9 +246,145 @@ impl<T: ggez::event::EventHandler> ggez::event::EventHandler for TestState<T> {
     }
 
     fn draw(&mut self, ctx: &mut Context) -> ggez::GameResult {
-        ggez::graphics::clear(ctx, [0.83, 0.69, 0.51, 1.0].into());
+        if self.canvas.is_none() {
+            self.canvas = Some(Canvas::with_window_size(ctx)?);
+        }
+        let canvas = self.canvas.as_ref().unwrap();
+
+        graphics::set_canvas(ctx, Some(canvas));
+        graphics::clear(ctx, [0.83, 0.69, 0.51, 1.0].into());
         self.element.draw(ctx)?;
-        ggez::graphics::present(ctx)?;
+        graphics::set_canvas(ctx, None);
+
+        // show what is being tested too
+        graphics::clear(ctx, graphics::BLACK);
+        graphics::draw(ctx, canvas, graphics::DrawParam::new())?;
+        graphics::present(ctx)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn a_missing_reference_fails_and_keeps_the_capture() {
+        let folder = scratch("missing_reference");
+        let reference = folder.join("expected.png");
+        let output = folder.join("output");
+
+        let result = check(
+            &reference,
+            &output,
+            &plain(1),
+            &Comparison::Exact,
+            &Mask::default(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(ScreenshotError::NoReferenceScreenshot { .. })
+        ));
+        assert!(!reference.exists());
+        assert_eq!(
+            load_reference(&output.join("actual.png")).unwrap(),
+            plain(1)
+        );
+    }
+
+    #[test]
+    fn a_mismatch_leaves_the_reference_alone() {
+        let folder = scratch("mismatch");
+        let reference = folder.join("expected.png");
+        let output = folder.join("output");
+        write_image(&reference, &plain(1)).unwrap();
+
+        let result = check(
+            &reference,
+            &output,
+            &plain(2),
+            &Comparison::Exact,
+            &Mask::default(),
+        );
+
+        match result {
+            Err(ScreenshotError::ScreenshotMismatch { diff, score, .. }) => {
+                assert_eq!(diff, Some(output.join("diff.png")));
+                assert_eq!(score, compare::Score::DifferingPixels(1.));
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+        assert_eq!(load_reference(&reference).unwrap(), plain(1));
+        assert_eq!(
+            load_reference(&output.join("actual.png")).unwrap(),
+            plain(2)
+        );
+        assert!(check(
+            &reference,
+            &output,
+            &plain(1),
+            &Comparison::Exact,
+            &Mask::default()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn reports_unreadable_references() {
+        let folder = scratch("unreadable");
+        let reference = folder.join("expected.png");
+        fs::write(&reference, b"not a png").unwrap();
+
+        let result = check(
+            &reference,
+            &folder,
+            &plain(1),
+            &Comparison::Exact,
+            &Mask::default(),
+        );
+
+        assert!(matches!(result, Err(ScreenshotError::DecodingError { .. })));
+    }
+
+    #[test]
+    fn crops_outside_the_capture_are_errors() {
+        let mask = Mask {
+            crop: Some(Area::new(2, 2, 3, 1)),
+            ignored: Vec::new(),
+        };
+
+        let result = screenshot_test(
+            "crop_outside",
+            4,
+            4,
+            plain(1).into_raw(),
+            &Comparison::Exact,
+            &mask,
+        );
+
+        match result {
+            Err(error @ ScreenshotError::CropOutsideCapture { .. }) => assert_eq!(
+                error.to_string(),
+                "the 3 by 1 crop at (2, 2) reaches outside the 4 by 4 capture"
+            ),
+            other => panic!("expected the crop to be refused, got {:?}", other),
+        }
+        assert!(!Path::new("target/test_ggez/crop_outside").exists());
+    }
+
+    /// An empty folder of its own for each test.
+    fn scratch(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir()
+            .join(format!("test_ggez_{}", std::process::id()))
+            .join(name);
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn plain(red: u8) -> RgbaImage {
+        RgbaImage::from_pixel(4, 4, Rgba([red, 0, 0, 255]))
+    }
+}
@@ -0,0 +1,122 @@
+use image::{imageops, Rgba, RgbaImage};
+
+/// What `diff.png` shows where the screenshots weren't compared.
+pub const MASK_COLOUR: Rgba<u8> = Rgba([255, 0, 255, 255]);
+
+/// A rectangle of the screenshot, in pixels from its top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Area {
+    pub fn new(x: u32, y: u32, w: u32, h: u32) -> Area {
+        Area { x, y, w, h }
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x - self.x < self.w && y >= self.y && y - self.y < self.h
+    }
+
+    /// Whether the area lies entirely within a `w` by `h` image.
+    pub fn fits(&self, w: u32, h: u32) -> bool {
+        self.x.checked_add(self.w).is_some_and(|right| right <= w)
+            && self.y.checked_add(self.h).is_some_and(|bottom| bottom <= h)
+    }
+}
+
+/// Parts of a screenshot that aren't compared, e.g. an FPS counter or a
+/// turret that follows the mouse. Only `crop` is compared if it is set, and
+/// never the `ignored` areas.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mask {
+    pub crop: Option<Area>,
+    pub ignored: Vec<Area>,
+}
+
+impl Mask {
+    /// Whether the pixel at `x`, `y` is left out of the comparison.
+    pub fn hides(&self, x: u32, y: u32) -> bool {
+        self.crop.is_some_and(|crop| !crop.contains(x, y))
+            || self.ignored.iter().any(|area| area.contains(x, y))
+    }
+
+    /// What of `image` is compared: the crop, with ignored areas blanked out
+    /// so they match whatever was drawn there.
+    pub fn apply(&self, image: &RgbaImage) -> RgbaImage {
+        let mut image = image.clone();
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            if self.ignored.iter().any(|area| area.contains(x, y)) {
+                *pixel = Rgba([0; 4]);
+            }
+        }
+
+        match self.crop {
+            Some(crop) => imageops::crop_imm(&image, crop.x, crop.y, crop.w, crop.h).to_image(),
+            None => image,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blanks_ignored_areas() {
+        let mask = Mask {
+            crop: None,
+            ignored: vec![Area::new(1, 0, 2, 1)],
+        };
+
+        let masked = mask.apply(&numbered(4, 2));
+
+        assert_eq!(reds(&masked), vec![0, 0, 0, 3, 4, 5, 6, 7]);
+        assert!(mask.hides(2, 0));
+        assert!(!mask.hides(3, 0));
+    }
+
+    #[test]
+    fn compares_only_the_crop() {
+        let mask = Mask {
+            crop: Some(Area::new(1, 1, 2, 1)),
+            ignored: vec![Area::new(0, 0, 2, 2)],
+        };
+
+        let masked = mask.apply(&numbered(4, 2));
+
+        assert_eq!(masked.dimensions(), (2, 1));
+        assert_eq!(reds(&masked), vec![0, 6]);
+        assert!(mask.hides(0, 0));
+        assert!(mask.hides(1, 1));
+        assert!(!mask.hides(2, 1));
+    }
+
+    #[test]
+    fn areas_reaching_the_edge_of_u32_dont_overflow() {
+        let area = Area::new(u32::MAX - 1, 0, 5, 1);
+
+        assert!(area.contains(u32::MAX, 0));
+        assert!(!area.contains(u32::MAX - 2, 0));
+        assert!(!area.fits(u32::MAX, 1));
+    }
+
+    #[test]
+    fn crops_fit_only_inside_the_image() {
+        assert!(Area::new(1, 1, 3, 1).fits(4, 2));
+        assert!(!Area::new(1, 1, 4, 1).fits(4, 2));
+        assert!(!Area::new(0, 2, 1, 1).fits(4, 2));
+    }
+
+    /// A `w` by `h` image whose pixels' red channel counts up from 0.
+    fn numbered(w: u32, h: u32) -> RgbaImage {
+        RgbaImage::from_fn(w, h, |x, y| Rgba([(y * w + x) as u8, 0, 0, 255]))
+    }
+
+    fn reds(image: &RgbaImage) -> Vec<u8> {
+        image.pixels().map(|pixel| pixel.0[0]).collect()
+    }
+}
@@ -1,48 +1,31 @@
-use scrap::{Capturer, Display};
-use std::io::ErrorKind::WouldBlock;
-use std::thread;
-use std::time::Duration;
+use ggez::graphics::Canvas;
+use ggez::{Context, GameResult};
 
-pub fn get_screenshot() -> (usize, usize, Vec<u8>) {
-    let one_second = Duration::new(1, 0);
-    let one_frame = one_second / 60;
+/// Width, height and RGBA pixels of what was drawn into `canvas`, top row first.
+pub fn capture(ctx: &mut Context, canvas: &Canvas) -> GameResult<(u32, u32, Vec<u8>)> {
+    let image = canvas.image();
+    let (w, h) = (u32::from(image.width()), u32::from(image.height()));
+    let pixels = image.to_rgba8(ctx)?;
 
-    let display = Display::primary().expect("Couldn't find primary display.");
-    let mut capturer = Capturer::new(display).expect("Couldn't begin capture.");
-    let (w, h) = (capturer.width(), capturer.height());
-    let mut bitflipped = Vec::with_capacity(w * h * 4);
-
-    loop {
-        // Wait until there's a frame.
-
-        let buffer = match capturer.frame() {
-            Ok(buffer) => buffer,
-            Err(error) => {
-                if error.kind() == WouldBlock {
-                    // Keep spinning.
-                    thread::sleep(one_frame);
-                    continue;
-                } else {
-                    panic!("Error: {}", error);
-                }
-            }
-        };
-
-        println!("Captured!");
+    Ok((w, h, flip_rows(&pixels, w as usize)))
+}
 
-        // Flip the ARGB image into a BGRA image.
+/// Canvases keep OpenGL's bottom row first, image files expect the top one.
+fn flip_rows(pixels: &[u8], w: usize) -> Vec<u8> {
+    pixels.chunks(w * 4).rev().flatten().copied().collect()
+}
 
-        let stride = buffer.len() / h;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        for y in 0..h {
-            for x in 0..w {
-                let i = stride * y + 4 * x;
-                bitflipped.extend_from_slice(&[buffer[i + 2], buffer[i + 1], buffer[i], 255]);
-            }
-        }
+    #[test]
+    fn rows_come_out_top_first() {
+        let bottom_up = [1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4];
 
-        break;
+        assert_eq!(
+            flip_rows(&bottom_up, 2),
+            vec![3, 3, 3, 3, 4, 4, 4, 4, 1, 1, 1, 1, 2, 2, 2, 2]
+        );
     }
-
-    (w, h, bitflipped)
 }